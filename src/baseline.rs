@@ -0,0 +1,212 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use log::{debug, info, warn};
+use crate::pe_strings::ExtractedString;
+use crate::{analyze_binary, Package, STD_DEPENDENCY_CRATES};
+
+pub const BASELINE_FILE: &str = "string_baseline.json";
+
+/// Shortest extracted string matched against the inside of a baseline string.
+const MIN_SUBSTRING_LEN: usize = 8;
+
+/// Known library strings keyed by crate, then version (`*` for any version).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct StringBaseline {
+    pub crates: BTreeMap<String, BTreeMap<String, BTreeSet<String>>>,
+}
+
+impl StringBaseline {
+    pub fn len(&self) -> usize {
+        self.strings().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn insert(&mut self, krate: &str, version: &str, string: &str) {
+        self.crates
+            .entry(krate.to_string())
+            .or_default()
+            .entry(version.to_string())
+            .or_default()
+            .insert(string.to_string());
+    }
+
+    pub fn merge(&mut self, other: StringBaseline) {
+        for (krate, versions) in other.crates {
+            let entry = self.crates.entry(krate).or_default();
+            for (version, strings) in versions {
+                entry.entry(version).or_default().extend(strings);
+            }
+        }
+    }
+
+    /// Crate among `linked` whose literal `string` is, or contains it when it
+    /// is long enough to be a tail of that literal.
+    pub fn owner_of(&self, string: &str, linked: &HashSet<String>) -> Option<&str> {
+        let owned = |matches: &dyn Fn(&String) -> bool| {
+            self.crates
+                .iter()
+                .filter(|(name, _)| linked.contains(&normalize(name)))
+                .find(|(_, versions)| versions.values().flatten().any(matches))
+                .map(|(name, _)| name.as_str())
+        };
+        owned(&|k| k == string).or_else(|| {
+            (string.len() >= MIN_SUBSTRING_LEN).then(|| owned(&|k| k.contains(string))).flatten()
+        })
+    }
+
+    fn strings(&self) -> impl Iterator<Item = &String> {
+        self.crates.values().flat_map(|versions| versions.values()).flatten()
+    }
+}
+
+pub fn load_string_baseline() -> Option<StringBaseline> {
+    match fs::read_to_string(BASELINE_FILE) {
+        Ok(content) => match serde_json::from_str::<StringBaseline>(&content) {
+            Ok(baseline) => Some(baseline),
+            Err(e) => {
+                warn!("Failed to parse {}: {}", BASELINE_FILE, e);
+                None
+            }
+        },
+        Err(e) => {
+            debug!("No string baseline loaded from {}: {}", BASELINE_FILE, e);
+            None
+        }
+    }
+}
+
+/// Baseline entries that apply to every binary, linked crates or not.
+const ALWAYS_LINKED: &[&str] = &["std", "msvc-crt"];
+
+fn normalize(name: &str) -> String {
+    name.to_ascii_lowercase().replace('_', "-")
+}
+
+/// Baseline crates that apply to a binary linking `packages`.
+pub fn linked_crates(packages: &[Package]) -> HashSet<String> {
+    packages
+        .iter()
+        .map(|p| normalize(&p.crate_name_and_version().0))
+        .chain(ALWAYS_LINKED.iter().map(|name| name.to_string()))
+        .collect()
+}
+
+/// Flag every string the baseline attributes to std, the C runtime or one of
+/// `packages` as `known_library`. Extracted strings are often the tail of a
+/// longer literal, so those count as well.
+pub fn mark_known_strings(strings: &mut [ExtractedString], baseline: &StringBaseline, packages: &[Package]) {
+    let linked = linked_crates(packages);
+    let known: HashSet<&str> = baseline
+        .crates
+        .iter()
+        .filter(|(name, _)| linked.contains(&normalize(name)))
+        .flat_map(|(_, versions)| versions.values())
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    for s in strings.iter_mut() {
+        s.known_library = known.contains(s.string.as_str())
+            || (s.string.len() >= MIN_SUBSTRING_LEN && known.iter().any(|k| k.contains(s.string.as_str())));
+    }
+    debug!(
+        "{} of {} language strings are known library strings",
+        strings.iter().filter(|s| s.known_library).count(),
+        strings.len()
+    );
+}
+
+/// A reference binary's linked `(crate, version)` pairs and language strings.
+type ReferenceBinary = (BTreeSet<(String, String)>, HashSet<String>);
+
+/// Attribute strings of compiled reference binaries to the crate they come
+/// from. A string belongs to the crate that every binary containing it links
+/// and that the fewest reference binaries link overall; strings with no single
+/// such crate are left out. A std-only binary plus one binary per crate of
+/// interest gives the cleanest attribution.
+pub fn build_baseline(dir: &Path, min_length: usize) -> Result<StringBaseline, Box<dyn std::error::Error>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+
+    let mut samples: Vec<ReferenceBinary> = Vec::new();
+    for path in paths {
+        let name = path.to_string_lossy().into_owned();
+        let result = match analyze_binary(&name, min_length) {
+            Ok(result) => result,
+            Err(e) => {
+                warn!("Skipping {}: {}", name, e);
+                continue;
+            }
+        };
+        if result.language_strings.is_empty() {
+            warn!("Skipping {}: no language strings extracted", name);
+            continue;
+        }
+        let mut crates: BTreeSet<(String, String)> = result
+            .packages
+            .iter()
+            .map(|p| p.crate_name_and_version())
+            .filter(|(name, _)| !STD_DEPENDENCY_CRATES.contains(&name.as_str()))
+            .map(|(name, version)| (name, version.unwrap_or_else(|| "*".to_string())))
+            .collect();
+        crates.insert(("std".to_string(), "*".to_string()));
+        let strings = result.language_strings.into_iter().map(|s| s.string).collect();
+        samples.push((crates, strings));
+    }
+    info!("Building string baseline from {} reference binaries", samples.len());
+
+    let mut crate_counts: HashMap<&str, usize> = HashMap::new();
+    for (crates, _) in &samples {
+        let names: BTreeSet<&str> = crates.iter().map(|(name, _)| name.as_str()).collect();
+        for name in names {
+            *crate_counts.entry(name).or_default() += 1;
+        }
+    }
+
+    let all_strings: BTreeSet<&String> = samples.iter().flat_map(|(_, strings)| strings.iter()).collect();
+    let mut baseline = StringBaseline::default();
+    for string in all_strings {
+        let mut containing = samples.iter().filter(|(_, strings)| strings.contains(string));
+        let Some((first, _)) = containing.next() else { continue };
+        let mut common: BTreeSet<(String, String)> = first.clone();
+        for (crates, _) in containing {
+            // Same crate at different versions: keep it, version unknown.
+            common = common
+                .iter()
+                .filter_map(|(name, version)| {
+                    crates.iter().find(|(other, _)| other == name).map(|(_, other_version)| {
+                        let version = if other_version == version { version.clone() } else { "*".to_string() };
+                        (name.clone(), version)
+                    })
+                })
+                .collect();
+        }
+
+        let Some(fewest) = common.iter().map(|(name, _)| crate_counts[name.as_str()]).min() else { continue };
+        let owners: Vec<&(String, String)> =
+            common.iter().filter(|(name, _)| crate_counts[name.as_str()] == fewest).collect();
+        if let [(name, version)] = owners.as_slice() {
+            baseline.insert(name, version, string);
+        }
+    }
+    Ok(baseline)
+}
+
+/// Build a baseline from `dir` and merge it into `string_baseline.json`.
+pub fn update_string_baseline(dir: &Path, min_length: usize) -> Result<usize, Box<dyn std::error::Error>> {
+    let built = build_baseline(dir, min_length)?;
+    let added = built.len();
+    let mut baseline = load_string_baseline().unwrap_or_default();
+    baseline.merge(built);
+    fs::write(BASELINE_FILE, serde_json::to_string_pretty(&baseline)? + "\n")?;
+    info!("Wrote {} strings to {}", baseline.len(), BASELINE_FILE);
+    Ok(added)
+}
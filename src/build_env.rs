@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use log::debug;
+use crate::toolchain_evidence::NameCount;
+use crate::STD_DEPENDENCY_CRATES;
+
+/// Workspace and home directories of hosted CI runners, as `(system, pattern)`.
+/// Azure Pipelines comes first: both use `D:\a\` on Windows.
+const CI_MARKERS: &[(&str, &str)] = &[
+    ("azure_pipelines", r"/home/vsts/work/"),
+    ("azure_pipelines", r"[A-Z]:\\a\\\d+\\s\\"),
+    ("github_actions", r"/home/runner/work/"),
+    ("github_actions", r"/Users/runner/work/"),
+    ("github_actions", r"(?i)[A-Z]:\\Users\\runneradmin\\"),
+    ("github_actions", r"[A-Z]:\\a\\[^\\\x00]+\\[^\\\x00]+\\"),
+    ("gitlab_ci", r"/builds/[A-Za-z0-9._\-]+/"),
+    ("gitlab_ci", r"(?i)[A-Z]:\\GitLab-Runner\\builds\\"),
+    ("jenkins", r"/var/lib/jenkins/workspace/"),
+    ("jenkins", r"/var/jenkins_home/workspace/"),
+    ("travis_ci", r"/home/travis/build/"),
+    ("circleci", r"/home/circleci/"),
+    ("buildkite", r"/var/lib/buildkite-agent/builds/"),
+];
+
+/// Cargo homes and vendor directories that imply a container or sandbox.
+const CONTAINER_MARKERS: &[(&str, &str)] = &[
+    ("rust_docker_image", "/usr/local/cargo/"),
+    ("cross", "/cargo/"),
+    ("nix", "/nix/store/"),
+    ("nix", "/build/cargo-vendor-dir/"),
+    ("root_container", "/root/.cargo/"),
+];
+
+/// Home directories of CI service accounts rather than people.
+const SERVICE_ACCOUNTS: &[&str] = &["circleci", "runner", "runneradmin", "travis", "vsts"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EnvironmentEvidence {
+    /// `ci`, `container`, `host_os` or `cargo_home`.
+    pub field: String,
+    pub value: String,
+    pub path: String,
+}
+
+/// Where the binary was built, from the paths the build left behind.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BuildEnvironment {
+    /// `github_actions`, `gitlab_ci`, `azure_pipelines`, `jenkins`, `travis_ci`,
+    /// `circleci` or `buildkite`.
+    pub ci: Option<String>,
+    /// `rust_docker_image` (`/usr/local/cargo`), `cross` (`/cargo`), `nix` or
+    /// `root_container` (`/root/.cargo`).
+    pub container: Option<String>,
+    /// `linux`, `macos` or `windows`.
+    pub host_os: Option<String>,
+    /// The cargo home most dependencies were fetched into.
+    pub cargo_home: Option<String>,
+    /// Every cargo home seen, most frequent first.
+    pub cargo_homes: Vec<NameCount>,
+    /// Account names from personal home directories, e.g. `C:\Users\<name>`.
+    pub user_names: Vec<String>,
+    /// A person's machine: a personal cargo home and no CI or container marker.
+    pub developer_workstation: bool,
+    pub evidence: Vec<EnvironmentEvidence>,
+}
+
+impl BuildEnvironment {
+    fn record(&mut self, field: &str, value: &str, path: &str) {
+        self.evidence.push(EnvironmentEvidence { field: field.to_string(), value: value.to_string(), path: path.to_string() });
+    }
+}
+
+/// Cargo homes and vendor directories, shared with source path categorization.
+pub(crate) fn is_cargo_home_path(path: &str) -> bool {
+    path.contains(".cargo")
+        || path.starts_with("/usr/local/cargo/")
+        || path.starts_with("/cargo/")
+        || path.starts_with("/build/cargo-vendor-dir/")
+        || (path.starts_with("/nix/store/") && path.contains("-vendor"))
+}
+
+fn host_os(path: &str) -> Option<&'static str> {
+    let bytes = path.as_bytes();
+    if bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        Some("windows")
+    } else if path.starts_with("/Users/") {
+        Some("macos")
+    } else if path.starts_with("/home/") || path.starts_with("/root/") || path.starts_with("/usr/local/cargo/") {
+        Some("linux")
+    } else {
+        None
+    }
+}
+
+fn user_name(path: &str) -> Option<String> {
+    let re = regex::Regex::new(r"^(?:[A-Za-z]:[\\/]Users|/Users|/home)[\\/]([^\\/]+)[\\/]").ok()?;
+    let name = re.captures(path)?.get(1)?.as_str();
+    (!SERVICE_ACCOUNTS.contains(&name)).then(|| name.to_string())
+}
+
+/// Cargo homes of registry paths, skipping crates std itself depends on: the
+/// prebuilt std carries the paths of the Rust project's own CI.
+fn cargo_homes(content: &[u8]) -> Result<Vec<NameCount>, Box<dyn std::error::Error>> {
+    let re = Regex::new(
+        r"((?:[A-Za-z]:)?[\\/](?:[A-Za-z0-9._\- ]+[\\/]){0,8}?\.?cargo)[\\/]registry[\\/]src[\\/][^\\/\x00]+[\\/]([A-Za-z0-9_\-]+?)-\d+\.\d+",
+    )?;
+    let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+    for (i, caps) in re.captures_iter(content).enumerate() {
+        let krate = String::from_utf8_lossy(&caps[2]).into_owned();
+        if STD_DEPENDENCY_CRATES.contains(&krate.as_str()) {
+            continue;
+        }
+        let home = String::from_utf8_lossy(&caps[1]).into_owned();
+        counts.entry(home).or_insert((0, i)).0 += 1;
+    }
+    let mut counts: Vec<(String, (usize, usize))> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.1 .1.cmp(&b.1 .1)));
+    Ok(counts.into_iter().map(|(name, (count, _))| NameCount { name, count }).collect())
+}
+
+/// Classify the CI system, container, build host OS and cargo home from
+/// workspace, home and registry paths.
+pub fn classify_build_environment(content: &[u8]) -> Result<BuildEnvironment, Box<dyn std::error::Error>> {
+    let mut env = BuildEnvironment { cargo_homes: cargo_homes(content)?, ..Default::default() };
+
+    for home in env.cargo_homes.clone() {
+        let path = format!("{}/", home.name.replace('\\', "/"));
+        if let Some(os) = host_os(&home.name) {
+            if env.host_os.is_none() {
+                env.host_os = Some(os.to_string());
+                env.record("host_os", os, &home.name);
+            }
+        }
+        if let Some(name) = user_name(&home.name) {
+            if !env.user_names.contains(&name) {
+                env.user_names.push(name);
+            }
+        }
+        if env.container.is_none() {
+            if let Some((container, _)) = CONTAINER_MARKERS.iter().find(|(_, prefix)| path.starts_with(prefix)) {
+                env.container = Some(container.to_string());
+                env.record("container", container, &home.name);
+            }
+        }
+    }
+    if let Some(home) = env.cargo_homes.first().map(|h| h.name.clone()) {
+        env.record("cargo_home", &home, &home);
+        env.cargo_home = Some(home);
+    }
+    if env.container.is_none() {
+        let nix_re = Regex::new(r"/nix/store/[a-z0-9]{32}-[A-Za-z0-9._\-]+/")?;
+        if let Some(mat) = nix_re.find(content) {
+            env.container = Some("nix".to_string());
+            env.record("container", "nix", &String::from_utf8_lossy(mat.as_bytes()));
+        }
+    }
+
+    for &(ci, pattern) in CI_MARKERS {
+        if let Some(mat) = Regex::new(pattern)?.find(content) {
+            let path = String::from_utf8_lossy(mat.as_bytes()).into_owned();
+            // Rust's own CI builds std on GitHub Actions; only count it
+            // when a dependency's cargo home agrees.
+            if path.contains("runneradmin") && !env.cargo_homes.iter().any(|h| h.name.contains("runneradmin")) {
+                continue;
+            }
+            env.ci = Some(ci.to_string());
+            env.record("ci", ci, &path);
+            if env.host_os.is_none() {
+                if let Some(os) = host_os(&path) {
+                    env.host_os = Some(os.to_string());
+                    env.record("host_os", os, &path);
+                }
+            }
+            break;
+        }
+    }
+
+    env.developer_workstation = env.ci.is_none()
+        && env.container.is_none()
+        && env.cargo_home.as_deref().and_then(user_name).is_some();
+    debug!(
+        "Build environment: ci {:?}, container {:?}, host {:?}, cargo home {:?}",
+        env.ci, env.container, env.host_os, env.cargo_home
+    );
+    Ok(env)
+}
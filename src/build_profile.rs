@@ -0,0 +1,263 @@
+use goblin::Object;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use log::debug;
+use crate::find_needles;
+
+/// Panics only compiled in with `overflow-checks`, on by default in the dev
+/// profile. Division and remainder overflow is checked in every profile.
+const OVERFLOW_CHECK_MESSAGES: &[&str] = &[
+    "attempt to add with overflow",
+    "attempt to subtract with overflow",
+    "attempt to multiply with overflow",
+    "attempt to negate with overflow",
+    "attempt to shift left with overflow",
+    "attempt to shift right with overflow",
+];
+
+/// std precondition checks inlined into code built with `debug-assertions`.
+const DEBUG_ASSERTION_MESSAGES: &[&str] = &[
+    "unsafe precondition(s) violated",
+    "is_aligned_and_not_null",
+    "slice::from_raw_parts requires the pointer to be aligned and non-null",
+];
+
+/// Paths, messages and symbols of the two panic runtimes, as `(strategy, marker)`.
+/// Only one runtime is linked; `panic = "abort"` drops panic_unwind entirely.
+const PANIC_RUNTIME_MARKERS: &[(&str, &str)] = &[
+    ("abort", "panic_abort/src/"),
+    ("abort", "panic_abort\\src\\"),
+    ("abort", "11panic_abort"),
+    ("unwind", "panic_unwind/src/"),
+    ("unwind", "panic_unwind\\src\\"),
+    ("unwind", "12panic_unwind"),
+    ("unwind", "Rust panics must be rethrown"),
+    ("unwind", "Rust cannot catch foreign exceptions"),
+];
+
+/// Fewer exported Rust functions than this in a symbol table suggests LTO,
+/// which internalizes everything but the entry point.
+const LTO_MAX_GLOBAL_RUST_FUNCTIONS: usize = 16;
+
+/// A symbol table with fewer Rust functions than this is too small to tell
+/// LTO from a binary that never had much to export.
+const LTO_MIN_RUST_FUNCTIONS: usize = 256;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProfileEvidence {
+    /// `profile`, `overflow_checks`, `debug_assertions`, `panic`, `debuginfo`, `stripped` or `lto`.
+    pub setting: String,
+    pub value: String,
+    pub source: String,
+}
+
+/// Cargo profile and codegen settings the binary appears to be built with.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BuildProfile {
+    /// `debug` or `release`.
+    pub profile: Option<String>,
+    pub overflow_checks: bool,
+    pub debug_assertions: bool,
+    /// `unwind` or `abort`.
+    pub panic_strategy: Option<String>,
+    /// `none`, `std` (only the prebuilt standard library's), `full` or
+    /// `external` (a PDB next to the binary).
+    pub debuginfo: Option<String>,
+    /// Symbol table removed. Unknown for formats that rarely carry one.
+    pub stripped: Option<bool>,
+    /// Likely built with link-time optimization.
+    pub lto: Option<bool>,
+    pub evidence: Vec<ProfileEvidence>,
+}
+
+impl BuildProfile {
+    fn record(&mut self, setting: &str, value: &str, source: String) {
+        self.evidence.push(ProfileEvidence { setting: setting.to_string(), value: value.to_string(), source });
+    }
+}
+
+fn is_rust_symbol(name: &str) -> bool {
+    name.starts_with("_ZN") || name.starts_with("_R")
+}
+
+/// What the container says about debuginfo sections, the symbol table and exports.
+struct ContainerInfo {
+    debug_sections: Vec<String>,
+    pdb: Option<String>,
+    stripped: Option<bool>,
+    global_rust_functions: Option<usize>,
+    rust_functions: Option<usize>,
+}
+
+fn read_container(content: &[u8]) -> ContainerInfo {
+    let mut info = ContainerInfo {
+        debug_sections: Vec::new(),
+        pdb: None,
+        stripped: None,
+        global_rust_functions: None,
+        rust_functions: None,
+    };
+    match Object::parse(content) {
+        Ok(Object::Elf(elf)) => {
+            for section in &elf.section_headers {
+                if let Some(name) = elf.shdr_strtab.get_at(section.sh_name) {
+                    if name.starts_with(".debug_") || name.starts_with(".zdebug_") {
+                        info.debug_sections.push(name.to_string());
+                    }
+                }
+            }
+            info.stripped = Some(elf.syms.is_empty());
+            if !elf.syms.is_empty() {
+                let functions: Vec<_> = elf
+                    .syms
+                    .iter()
+                    .filter(|sym| sym.is_function() && sym.st_value != 0)
+                    .filter(|sym| elf.strtab.get_at(sym.st_name).is_some_and(is_rust_symbol))
+                    .collect();
+                info.rust_functions = Some(functions.len());
+                info.global_rust_functions =
+                    Some(functions.iter().filter(|sym| sym.st_bind() == goblin::elf::sym::STB_GLOBAL).count());
+            }
+        }
+        Ok(Object::PE(pe)) => {
+            for section in &pe.sections {
+                let name = String::from_utf8_lossy(&section.name).trim_end_matches('\0').to_string();
+                if name.starts_with(".debug_") {
+                    info.debug_sections.push(name);
+                }
+            }
+            info.pdb = pe
+                .debug_data
+                .as_ref()
+                .and_then(|d| d.codeview_pdb70_debug_info.as_ref())
+                .map(|cv| String::from_utf8_lossy(cv.filename).trim_end_matches('\0').to_string());
+            // MSVC never writes a COFF symbol table, so only its presence tells anything.
+            if pe.header.coff_header.number_of_symbol_table > 0 {
+                info.stripped = Some(false);
+            }
+        }
+        Ok(Object::Mach(goblin::mach::Mach::Binary(macho))) => {
+            for (section, _) in macho.segments.sections().flatten().flatten() {
+                if let Ok(name) = section.name() {
+                    if name.starts_with("__debug_") {
+                        info.debug_sections.push(name.to_string());
+                    }
+                }
+            }
+            info.stripped = Some(macho.symbols().next().is_none());
+        }
+        _ => {}
+    }
+    info.debug_sections.sort();
+    info.debug_sections.dedup();
+    info
+}
+
+/// Crates with a DWARF compile unit, from names like `src/main.rs/@/app.1a2b-cgu.0`.
+fn compile_unit_crates(content: &[u8]) -> Result<Vec<(String, bool)>, Box<dyn std::error::Error>> {
+    let re = Regex::new(r"(?-u)([^\x00]{1,4096})/@/([A-Za-z0-9_]+)\.[0-9a-f]+-cgu\.\d+")?;
+    let mut crates: Vec<(String, bool)> = Vec::new();
+    for caps in re.captures_iter(content) {
+        let path = String::from_utf8_lossy(&caps[1]).replace('\\', "/");
+        let name = String::from_utf8_lossy(&caps[2]).into_owned();
+        let prebuilt = crate::pe_strings::is_std_location(&path);
+        if !crates.iter().any(|(n, _)| *n == name) {
+            crates.push((name, prebuilt));
+        }
+    }
+    Ok(crates)
+}
+
+/// Infer the profile, panic strategy, debuginfo, stripping and LTO from
+/// embedded panic messages, runtime crates, sections and the symbol table.
+pub fn detect_build_profile(content: &[u8]) -> Result<BuildProfile, Box<dyn std::error::Error>> {
+    let mut profile = BuildProfile::default();
+    let needles: Vec<&[u8]> = OVERFLOW_CHECK_MESSAGES
+        .iter()
+        .chain(DEBUG_ASSERTION_MESSAGES)
+        .chain(PANIC_RUNTIME_MARKERS.iter().map(|(_, marker)| marker))
+        .map(|needle| needle.as_bytes())
+        .collect();
+    let found = find_needles(content, &needles);
+    let contains = |needle: &str| found.contains(needle.as_bytes());
+
+    if let Some(message) = OVERFLOW_CHECK_MESSAGES.iter().find(|m| contains(m)) {
+        profile.overflow_checks = true;
+        profile.record("overflow_checks", "true", format!("panic message {:?}", message));
+    }
+    if let Some(message) = DEBUG_ASSERTION_MESSAGES.iter().find(|m| contains(m)) {
+        profile.debug_assertions = true;
+        profile.record("debug_assertions", "true", format!("panic message {:?}", message));
+    }
+
+    // Cargo's output directory shows up in PDB paths and build script OUT_DIRs.
+    let target_dir_re = Regex::new(r"(?i)[\\/]target[\\/](?:[a-z0-9_\-]+[\\/])?(debug|release)[\\/]")?;
+    let target_dir = target_dir_re.captures(content).map(|caps| {
+        (String::from_utf8_lossy(&caps[1]).to_ascii_lowercase(), String::from_utf8_lossy(&caps[0]).into_owned())
+    });
+    let inferred = match (&target_dir, profile.overflow_checks || profile.debug_assertions) {
+        (Some((dir, path)), _) => {
+            profile.record("profile", dir, format!("cargo target directory {}", path));
+            dir.clone()
+        }
+        (None, true) => {
+            profile.record("profile", "debug", "overflow checks or debug assertions".to_string());
+            "debug".to_string()
+        }
+        (None, false) => {
+            profile.record("profile", "release", "no overflow-check or debug-assertion panics".to_string());
+            "release".to_string()
+        }
+    };
+    profile.profile = Some(inferred);
+
+    for &(strategy, marker) in PANIC_RUNTIME_MARKERS {
+        if contains(marker) {
+            profile.panic_strategy = Some(strategy.to_string());
+            profile.record("panic", strategy, format!("{} runtime marker {:?}", strategy, marker));
+            break;
+        }
+    }
+
+    let container = read_container(content);
+    let compile_units = compile_unit_crates(content)?;
+    let debuginfo = if let Some((name, _)) = compile_units.iter().find(|(_, prebuilt)| !prebuilt) {
+        profile.record("debuginfo", "full", format!("DWARF compile unit of crate {}", name));
+        "full"
+    } else if !container.debug_sections.is_empty() {
+        profile.record("debuginfo", "std", format!("only prebuilt std in {}", container.debug_sections.join(", ")));
+        "std"
+    } else if let Some(pdb) = &container.pdb {
+        profile.record("debuginfo", "external", format!("PDB {}", pdb));
+        "external"
+    } else {
+        "none"
+    };
+    profile.debuginfo = Some(debuginfo.to_string());
+
+    profile.stripped = container.stripped;
+    if let Some(stripped) = container.stripped {
+        let source = if stripped { "no symbol table" } else { "symbol table present" };
+        profile.record("stripped", &stripped.to_string(), source.to_string());
+    }
+    // Few exports alone also fit a small binary, so LTO needs many internal functions too.
+    if let (Some(global), Some(total)) = (container.global_rust_functions, container.rust_functions) {
+        let lto = if global >= LTO_MAX_GLOBAL_RUST_FUNCTIONS {
+            Some(false)
+        } else if total >= LTO_MIN_RUST_FUNCTIONS {
+            Some(true)
+        } else {
+            None
+        };
+        if let Some(lto) = lto {
+            profile.lto = Some(lto);
+            profile.record("lto", &lto.to_string(), format!("{} of {} Rust functions global", global, total));
+        }
+    }
+
+    debug!(
+        "Build profile {:?}, panic {:?}, debuginfo {:?}, stripped {:?}, lto {:?}",
+        profile.profile, profile.panic_strategy, profile.debuginfo, profile.stripped, profile.lto
+    );
+    Ok(profile)
+}
@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+use log::debug;
+use crate::Package;
+use crate::std_api::std_relative_path;
+
+/// Crate and std source file indicators, maintained in `capabilities.json`.
+const CAPABILITY_RULES: &str = include_str!("../capabilities.json");
+
+#[derive(Deserialize, Debug)]
+struct CapabilityRule {
+    capability: String,
+    #[serde(default)]
+    attack: Vec<String>,
+    #[serde(default)]
+    crates: Vec<String>,
+    #[serde(default)]
+    std_paths: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Capability {
+    pub name: String,
+    /// MITRE ATT&CK technique IDs associated with the capability.
+    pub attack: Vec<String>,
+    /// Indicators that triggered it, e.g. `crate:reqwest` or `std:std/src/net/tcp.rs`.
+    pub evidence: Vec<String>,
+}
+
+/// The embedded table, parsed on first use.
+fn load_rules() -> Result<&'static [CapabilityRule], Box<dyn std::error::Error>> {
+    static RULES: OnceLock<Result<Vec<CapabilityRule>, String>> = OnceLock::new();
+    RULES
+        .get_or_init(|| serde_json::from_str(CAPABILITY_RULES).map_err(|e| e.to_string()))
+        .as_deref()
+        .map_err(|e| format!("invalid embedded capabilities.json: {}", e).into())
+}
+
+pub fn infer_capabilities<'a>(
+    packages: &[Package],
+    framework_paths: impl IntoIterator<Item = &'a String>,
+) -> Result<Vec<Capability>, Box<dyn std::error::Error>> {
+    let rules = load_rules()?;
+    let std_paths: Vec<String> = framework_paths.into_iter().map(|p| std_relative_path(p)).collect();
+    let crate_names: Vec<String> = packages.iter().map(|p| p.crate_name_and_version().0).collect();
+
+    let mut found: BTreeMap<String, Capability> = BTreeMap::new();
+    for rule in rules {
+        let mut evidence: Vec<String> = Vec::new();
+        for name in &crate_names {
+            if rule.crates.iter().any(|c| c == name) {
+                evidence.push(format!("crate:{}", name));
+            }
+        }
+        for path in &std_paths {
+            if rule.std_paths.iter().any(|p| path.contains(p.as_str())) {
+                evidence.push(format!("std:{}", path));
+            }
+        }
+        if evidence.is_empty() {
+            continue;
+        }
+        evidence.sort();
+        evidence.dedup();
+        let capability = found.entry(rule.capability.clone()).or_insert_with(|| Capability {
+            name: rule.capability.clone(),
+            attack: Vec::new(),
+            evidence: Vec::new(),
+        });
+        capability.attack.extend(rule.attack.iter().cloned());
+        capability.evidence.extend(evidence);
+    }
+
+    debug!("Inferred {} capabilities", found.len());
+    Ok(found.into_values().collect())
+}
@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use serde::Serialize;
+use log::{debug, warn};
+use crate::fingerprint::{crate_similarity, jaccard};
+use crate::{load_or_analyze, AnalysisResult};
+
+/// Jaccard similarity at which two samples are linked.
+pub const DEFAULT_CLUSTER_THRESHOLD: f64 = 0.7;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterKind {
+    UserPaths,
+    CrateSet,
+    RustcHash,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Cluster {
+    pub kind: ClusterKind,
+    pub members: Vec<String>,
+    /// Features every member has: user source paths, crate names, or the
+    /// rustc hash and resolved version.
+    pub shared: Vec<String>,
+    /// Lowest pairwise similarity between members.
+    pub min_similarity: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ClusterSample {
+    pub path: String,
+    pub sha256: String,
+    pub crate_hash: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SkippedSample {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ClusterReport {
+    pub threshold: f64,
+    pub samples: Vec<ClusterSample>,
+    pub clusters: Vec<Cluster>,
+    pub skipped: Vec<SkippedSample>,
+}
+
+fn find(parent: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    parent[i] = root;
+    root
+}
+
+/// Single-linkage grouping of the samples with a non-empty feature set.
+fn link_by_similarity(sets: &[Vec<String>], threshold: f64) -> Vec<Vec<usize>> {
+    let candidates: Vec<usize> = (0..sets.len()).filter(|&i| !sets[i].is_empty()).collect();
+    let mut parent: Vec<usize> = (0..sets.len()).collect();
+    for (n, &i) in candidates.iter().enumerate() {
+        for &j in &candidates[n + 1..] {
+            if jaccard(&sets[i], &sets[j]) >= threshold {
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in candidates {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(i);
+    }
+    groups.into_values().filter(|g| g.len() > 1).collect()
+}
+
+fn shared_features(sets: &[Vec<String>], members: &[usize]) -> Vec<String> {
+    let mut shared: BTreeSet<&String> = sets[members[0]].iter().collect();
+    for &i in &members[1..] {
+        let other: BTreeSet<&String> = sets[i].iter().collect();
+        shared = shared.intersection(&other).copied().collect();
+    }
+    shared.into_iter().cloned().collect()
+}
+
+fn min_pairwise(members: &[usize], similarity: impl Fn(usize, usize) -> f64) -> f64 {
+    let mut min: f64 = 1.0;
+    for (n, &i) in members.iter().enumerate() {
+        for &j in &members[n + 1..] {
+            min = min.min(similarity(i, j));
+        }
+    }
+    (min * 100.0).round() / 100.0
+}
+
+/// Cluster named analysis results by user source paths, crate set and rustc hash.
+pub fn cluster_results(samples: &[(String, AnalysisResult)], threshold: f64) -> Vec<Cluster> {
+    let names = |members: &[usize]| members.iter().map(|&i| samples[i].0.clone()).collect::<Vec<_>>();
+    let mut clusters = Vec::new();
+
+    let user_paths: Vec<Vec<String>> = samples
+        .iter()
+        .map(|(_, r)| {
+            let mut paths: Vec<String> = r.user_source_paths.iter().map(|p| p.replace('\\', "/")).collect();
+            paths.sort();
+            paths.dedup();
+            paths
+        })
+        .collect();
+    for members in link_by_similarity(&user_paths, threshold) {
+        clusters.push(Cluster {
+            kind: ClusterKind::UserPaths,
+            members: names(&members),
+            shared: shared_features(&user_paths, &members),
+            min_similarity: min_pairwise(&members, |i, j| jaccard(&user_paths[i], &user_paths[j])),
+        });
+    }
+
+    let crate_sets: Vec<Vec<String>> = samples.iter().map(|(_, r)| r.crate_fingerprint.crates.clone()).collect();
+    for members in link_by_similarity(&crate_sets, threshold) {
+        clusters.push(Cluster {
+            kind: ClusterKind::CrateSet,
+            members: names(&members),
+            shared: shared_features(&crate_sets, &members),
+            min_similarity: min_pairwise(&members, |i, j| {
+                crate_similarity(&samples[i].1.crate_fingerprint, &samples[j].1.crate_fingerprint)
+            }),
+        });
+    }
+
+    let mut by_hash: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (i, (_, result)) in samples.iter().enumerate() {
+        if let Some(hash) = &result.rustc_hash {
+            by_hash.entry(hash).or_default().push(i);
+        }
+    }
+    for (hash, members) in by_hash.into_iter().filter(|(_, m)| m.len() > 1) {
+        let mut shared = vec![format!("rustc:{}", hash)];
+        if let Some(version) = &samples[members[0]].1.rust_version {
+            shared.push(format!("rust_version:{}", version));
+        }
+        clusters.push(Cluster {
+            kind: ClusterKind::RustcHash,
+            members: names(&members),
+            shared,
+            min_similarity: 1.0,
+        });
+    }
+
+    clusters.sort_by(|a, b| (a.kind, &a.members).cmp(&(b.kind, &b.members)));
+    clusters
+}
+
+/// Analyze every file in `dir` (binaries or saved `.json` reports) and cluster them.
+pub fn cluster_directory(dir: &Path, min_length: usize, threshold: f64) -> Result<ClusterReport, Box<dyn std::error::Error>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+
+    let mut samples = Vec::new();
+    let mut skipped = Vec::new();
+    for path in paths {
+        let name = path.to_string_lossy().into_owned();
+        match load_or_analyze(&name, min_length) {
+            Ok(result) => samples.push((name, result)),
+            Err(e) => {
+                warn!("Skipping {}: {}", name, e);
+                skipped.push(SkippedSample { path: name, error: e.to_string() });
+            }
+        }
+    }
+    debug!("Clustering {} samples from {}", samples.len(), dir.display());
+
+    let clusters = cluster_results(&samples, threshold);
+    Ok(ClusterReport {
+        threshold,
+        samples: samples
+            .iter()
+            .map(|(path, result)| ClusterSample {
+                path: path.clone(),
+                sha256: result.file.sha256.clone(),
+                crate_hash: result.crate_fingerprint.crate_hash.clone(),
+            })
+            .collect(),
+        clusters,
+        skipped,
+    })
+}
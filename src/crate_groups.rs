@@ -0,0 +1,76 @@
+use std::collections::{BTreeMap, HashSet};
+use serde::{Deserialize, Serialize};
+use crate::Package;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CrateVersion {
+    /// `None` when the registry directory carried no readable version.
+    pub version: Option<String>,
+    /// Registry directories this version was found under.
+    pub paths: Vec<String>,
+    /// Number of distinct embedded source files under those directories.
+    pub source_files: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CrateGroup {
+    pub name: String,
+    pub versions: Vec<CrateVersion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DuplicateSummary {
+    pub distinct_crates: usize,
+    pub duplicated_crates: usize,
+    /// Versions beyond the first for every duplicated crate.
+    pub extra_versions: usize,
+    pub names: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CrateGroupReport {
+    pub groups: Vec<CrateGroup>,
+    pub duplicates: DuplicateSummary,
+}
+
+pub fn group_packages(
+    packages: &[Package],
+    framework_paths: &HashSet<String>,
+    user_paths: &HashSet<String>,
+) -> CrateGroupReport {
+    let mut by_name: BTreeMap<String, BTreeMap<Option<String>, Vec<&Package>>> = BTreeMap::new();
+    for package in packages {
+        let (name, version) = package.crate_name_and_version();
+        by_name.entry(name).or_default().entry(version).or_default().push(package);
+    }
+
+    let groups: Vec<CrateGroup> = by_name
+        .into_iter()
+        .map(|(name, versions)| CrateGroup {
+            name,
+            versions: versions
+                .into_iter()
+                .map(|(version, packages)| {
+                    let source_files = framework_paths
+                        .iter()
+                        .chain(user_paths.iter())
+                        .filter(|path| packages.iter().any(|p| p.contains_source_path(path)))
+                        .count();
+                    let mut paths: Vec<String> = packages.iter().map(|p| p.path.clone()).collect();
+                    paths.sort();
+                    CrateVersion { version, paths, source_files }
+                })
+                .collect(),
+        })
+        .collect();
+
+    let duplicated: Vec<&CrateGroup> = groups.iter().filter(|g| g.versions.len() > 1).collect();
+    let duplicates = DuplicateSummary {
+        distinct_crates: groups.len(),
+        duplicated_crates: duplicated.len(),
+        extra_versions: duplicated.iter().map(|g| g.versions.len() - 1).sum(),
+        names: duplicated.iter().map(|g| g.name.clone()).collect(),
+    };
+
+    CrateGroupReport { groups, duplicates }
+}
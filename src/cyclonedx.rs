@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use crate::{to_hex, AnalysisResult, Package};
+
+const SPEC_VERSION: &str = "1.5";
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Bom {
+    pub bom_format: &'static str,
+    pub spec_version: &'static str,
+    pub serial_number: String,
+    pub version: u32,
+    pub metadata: Metadata,
+    pub components: Vec<Component>,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Metadata {
+    pub tools: Tools,
+    pub component: Component,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Tools {
+    pub components: Vec<Component>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Component {
+    #[serde(rename = "type")]
+    pub component_type: &'static str,
+    #[serde(rename = "bom-ref", skip_serializing_if = "Option::is_none")]
+    pub bom_ref: Option<String>,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purl: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hashes: Vec<Hash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evidence: Option<Evidence>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub properties: Vec<Property>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Hash {
+    pub alg: &'static str,
+    pub content: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Evidence {
+    pub occurrences: Vec<Occurrence>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Occurrence {
+    pub location: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Property {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Dependency {
+    #[serde(rename = "ref")]
+    pub dependency_ref: String,
+    pub depends_on: Vec<String>,
+}
+
+/// Source paths a package was inferred from: its registry directory plus any
+/// embedded source files found beneath it.
+fn package_locations(package: &Package, result: &AnalysisResult) -> Vec<String> {
+    let mut locations = vec![package.path.clone()];
+    let mut files: Vec<&String> = result
+        .framework_source_paths
+        .iter()
+        .chain(result.user_source_paths.iter())
+        .filter(|p| package.contains_source_path(p))
+        .collect();
+    files.sort();
+    locations.extend(files.into_iter().cloned());
+    locations
+}
+
+/// Derive a stable urn:uuid from the file hash so repeated runs on the same
+/// binary produce the same serial number. Reports saved without the hash
+/// fall back to one over the file name and package list.
+fn serial_number(result: &AnalysisResult) -> String {
+    let sha256 = &result.file.sha256;
+    let hash = if sha256.len() >= 32 && sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+        sha256.to_ascii_lowercase()
+    } else {
+        let mut purls: Vec<String> = result.packages.iter().map(|p| p.purl()).collect();
+        purls.sort();
+        let seed = format!("{}\0{}", result.file.name, purls.join("\0"));
+        to_hex(&Sha256::digest(seed.as_bytes()))
+    };
+    format!(
+        "urn:uuid:{}-{}-5{}-8{}-{}",
+        &hash[0..8], &hash[8..12], &hash[13..16], &hash[17..20], &hash[20..32]
+    )
+}
+
+fn rustc_component(result: &AnalysisResult) -> Option<Component> {
+    if result.rustc_hash.is_none() && result.rust_version.is_none() {
+        return None;
+    }
+    let mut properties: Vec<Property> = result
+        .rustc_hash
+        .iter()
+        .map(|hash| Property { name: "rustre:rustc_commit_hash".to_string(), value: hash.clone() })
+        .collect();
+    if let Some(toolchain) = &result.toolchain {
+        properties.push(Property { name: "rustre:rustc_channel".to_string(), value: toolchain.channel.clone() });
+        if let Some(date) = &toolchain.date {
+            properties.push(Property { name: "rustre:rustc_date".to_string(), value: date.clone() });
+        }
+        if let Some(llvm) = toolchain.llvm_major {
+            properties.push(Property { name: "rustre:llvm_major".to_string(), value: llvm.to_string() });
+        }
+    }
+    Some(Component {
+        component_type: "application",
+        bom_ref: None,
+        name: "rustc".to_string(),
+        version: result.rust_version.clone(),
+        purl: None,
+        hashes: Vec::new(),
+        evidence: None,
+        properties,
+    })
+}
+
+pub fn to_cyclonedx(result: &AnalysisResult) -> Bom {
+    let file_ref = format!("file:{}", result.file.sha256);
+
+    // The same crate may be found under several registry roots; merge those
+    // into a single component so bom-refs stay unique.
+    let mut by_purl: BTreeMap<String, (&Package, Vec<String>)> = BTreeMap::new();
+    for package in &result.packages {
+        let locations = package_locations(package, result);
+        by_purl
+            .entry(package.purl())
+            .and_modify(|(_, existing)| existing.extend(locations.iter().cloned()))
+            .or_insert((package, locations));
+    }
+
+    let components: Vec<Component> = by_purl
+        .into_iter()
+        .map(|(purl, (package, mut locations))| {
+            locations.sort();
+            locations.dedup();
+            let (name, version) = package.crate_name_and_version();
+            Component {
+                component_type: "library",
+                bom_ref: Some(purl.clone()),
+                name,
+                version,
+                purl: Some(purl),
+                hashes: Vec::new(),
+                evidence: Some(Evidence {
+                    occurrences: locations.into_iter().map(|location| Occurrence { location }).collect(),
+                }),
+                properties: Vec::new(),
+            }
+        })
+        .collect();
+
+    let mut tools = vec![Component {
+        component_type: "application",
+        bom_ref: None,
+        name: env!("CARGO_PKG_NAME").to_string(),
+        version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        purl: None,
+        hashes: Vec::new(),
+        evidence: None,
+        properties: Vec::new(),
+    }];
+    tools.extend(rustc_component(result));
+
+    let dependencies = vec![Dependency {
+        dependency_ref: file_ref.clone(),
+        depends_on: components.iter().filter_map(|c| c.bom_ref.clone()).collect(),
+    }];
+
+    Bom {
+        bom_format: "CycloneDX",
+        spec_version: SPEC_VERSION,
+        serial_number: serial_number(result),
+        version: 1,
+        metadata: Metadata {
+            tools: Tools { components: tools },
+            component: Component {
+                component_type: "application",
+                bom_ref: Some(file_ref),
+                name: result.file.name.clone(),
+                version: None,
+                purl: None,
+                hashes: vec![
+                    Hash { alg: "SHA-1", content: result.file.sha1.clone() },
+                    Hash { alg: "SHA-256", content: result.file.sha256.clone() },
+                ],
+                evidence: None,
+                properties: Vec::new(),
+            },
+        },
+        components,
+        dependencies,
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, BTreeSet};
+use serde::Serialize;
+use crate::{AnalysisResult, Package};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SampleId {
+    pub name: String,
+    pub sha256: String,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SetDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct VersionChange {
+    pub name: String,
+    /// `None` entries are versions the registry path did not carry.
+    pub old_versions: Vec<Option<String>>,
+    pub new_versions: Vec<Option<String>>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CrateDiff {
+    /// Crates only linked into the new binary, as `name@version`.
+    pub added: Vec<String>,
+    /// Crates only linked into the old binary, as `name@version`.
+    pub removed: Vec<String>,
+    /// Crates in both whose set of linked versions differs.
+    pub changed: Vec<VersionChange>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ToolchainChange {
+    pub old_rustc_hash: Option<String>,
+    pub new_rustc_hash: Option<String>,
+    pub old_rust_version: Option<String>,
+    pub new_rust_version: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AnalysisDiff {
+    pub old: SampleId,
+    pub new: SampleId,
+    pub crates: CrateDiff,
+    /// `None` when both binaries were built by the same rustc.
+    pub toolchain: Option<ToolchainChange>,
+    pub user_source_paths: SetDiff,
+    pub language_strings: SetDiff,
+    pub unchanged: bool,
+}
+
+fn crate_versions(packages: &[Package]) -> BTreeMap<String, BTreeSet<Option<String>>> {
+    let mut crates: BTreeMap<String, BTreeSet<Option<String>>> = BTreeMap::new();
+    for package in packages {
+        let (name, version) = package.crate_name_and_version();
+        crates.entry(name).or_default().insert(version);
+    }
+    crates
+}
+
+fn display_crate(name: &str, version: &Option<String>) -> String {
+    match version {
+        Some(version) => format!("{}@{}", name, version),
+        None => name.to_string(),
+    }
+}
+
+fn diff_crates(old: &[Package], new: &[Package]) -> CrateDiff {
+    let old = crate_versions(old);
+    let new = crate_versions(new);
+    let mut diff = CrateDiff::default();
+
+    for (name, versions) in &new {
+        match old.get(name) {
+            None => diff.added.extend(versions.iter().map(|v| display_crate(name, v))),
+            Some(old_versions) if old_versions != versions => diff.changed.push(VersionChange {
+                name: name.clone(),
+                old_versions: old_versions.iter().cloned().collect(),
+                new_versions: versions.iter().cloned().collect(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (name, versions) in &old {
+        if !new.contains_key(name) {
+            diff.removed.extend(versions.iter().map(|v| display_crate(name, v)));
+        }
+    }
+    diff
+}
+
+fn diff_sets<'a>(old: impl IntoIterator<Item = &'a String>, new: impl IntoIterator<Item = &'a String>) -> SetDiff {
+    let old: BTreeSet<&String> = old.into_iter().collect();
+    let new: BTreeSet<&String> = new.into_iter().collect();
+    SetDiff {
+        added: new.difference(&old).map(|s| s.to_string()).collect(),
+        removed: old.difference(&new).map(|s| s.to_string()).collect(),
+    }
+}
+
+/// Normalize separators so a path moved between hosts does not show as changed.
+fn normalized_paths(result: &AnalysisResult) -> Vec<String> {
+    result.user_source_paths.iter().map(|p| p.replace('\\', "/")).collect()
+}
+
+pub fn diff_results(old: &AnalysisResult, new: &AnalysisResult) -> AnalysisDiff {
+    let crates = diff_crates(&old.packages, &new.packages);
+    let toolchain = (old.rustc_hash != new.rustc_hash || old.rust_version != new.rust_version).then(|| {
+        ToolchainChange {
+            old_rustc_hash: old.rustc_hash.clone(),
+            new_rustc_hash: new.rustc_hash.clone(),
+            old_rust_version: old.rust_version.clone(),
+            new_rust_version: new.rust_version.clone(),
+        }
+    });
+    let user_source_paths = diff_sets(&normalized_paths(old), &normalized_paths(new));
+    let language_strings = diff_sets(
+        old.language_strings.iter().map(|s| &s.string),
+        new.language_strings.iter().map(|s| &s.string),
+    );
+
+    let unchanged = crates.added.is_empty()
+        && crates.removed.is_empty()
+        && crates.changed.is_empty()
+        && toolchain.is_none()
+        && user_source_paths.added.is_empty()
+        && user_source_paths.removed.is_empty()
+        && language_strings.added.is_empty()
+        && language_strings.removed.is_empty();
+
+    AnalysisDiff {
+        old: SampleId { name: old.file.name.clone(), sha256: old.file.sha256.clone() },
+        new: SampleId { name: new.file.name.clone(), sha256: new.file.sha256.clone() },
+        crates,
+        toolchain,
+        user_source_paths,
+        language_strings,
+        unchanged,
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::{HashMap, HashSet};
+use goblin::Object;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use log::debug;
+use crate::Package;
+
+/// Number of source files at which the source-file signal saturates.
+const SOURCE_FILE_SATURATION: f64 = 20.0;
+/// Bounds a `(file, line, col)` record must fall in to count as a panic `Location`.
+const MAX_LOCATION_PATH_LEN: u64 = 1024;
+const MAX_LOCATION_LINE: u32 = 1_000_000;
+const MAX_LOCATION_COL: u32 = 10_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PackageEvidence {
+    pub path: String,
+    pub name: String,
+    pub version: String,
+    /// Distinct embedded source files under the package directory.
+    pub source_files: usize,
+    /// File offsets of every registry path match for this package.
+    pub offsets: Vec<usize>,
+    /// A mangled symbol rooted in this crate was found.
+    pub symbols_seen: bool,
+    /// A panic `Location` record (file, line, column) points at one of the
+    /// crate's source files, so its code is compiled in.
+    pub panic_locations_seen: bool,
+    /// Score in `0.0..=1.0` combining the signals above.
+    pub confidence: f64,
+}
+
+/// Collect crate identifiers that root mangled symbols: the first path
+/// component of legacy `_ZN` names and the crate root of v0 `_R` names.
+fn symbol_crates(content: &[u8]) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    let re = Regex::new(r"(?:_ZN|Cs[0-9A-Za-z]{0,16}_)(\d{1,3})")?;
+    let mut crates = HashSet::new();
+    for caps in re.captures_iter(content) {
+        let (Some(whole), Some(len)) = (caps.get(0), caps.get(1)) else { continue };
+        let Some(len) = std::str::from_utf8(len.as_bytes()).ok().and_then(|l| l.parse::<usize>().ok()) else {
+            continue;
+        };
+        let start = whole.end();
+        if len == 0 || start + len > content.len() {
+            continue;
+        }
+        let ident = &content[start..start + len];
+        if ident.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'_') {
+            crates.insert(String::from_utf8_lossy(ident).into_owned());
+        }
+    }
+    debug!("Found {} crate roots in mangled symbols", crates.len());
+    Ok(crates)
+}
+
+/// Loaded segments as `(virtual address, size, file offset)`, with the
+/// pointer width and byte order of the image.
+struct AddressMap {
+    segments: Vec<(u64, u64, u64)>,
+    pointer_size: usize,
+    little_endian: bool,
+    /// ELF `R_*_RELATIVE` addends by address: PIE images leave those pointers
+    /// zeroed in the file.
+    relocated: HashMap<u64, u64>,
+}
+
+impl AddressMap {
+    fn parse(content: &[u8]) -> Option<Self> {
+        match Object::parse(content).ok()? {
+            Object::Elf(elf) => Some(AddressMap {
+                segments: elf
+                    .program_headers
+                    .iter()
+                    .filter(|ph| ph.p_type == goblin::elf::program_header::PT_LOAD)
+                    .map(|ph| (ph.p_vaddr, ph.p_filesz, ph.p_offset))
+                    .collect(),
+                pointer_size: if elf.is_64 { 8 } else { 4 },
+                little_endian: elf.little_endian,
+                relocated: elf
+                    .dynrelas
+                    .iter()
+                    .filter(|r| r.r_sym == 0)
+                    .filter_map(|r| Some((r.r_offset, u64::try_from(r.r_addend?).ok()?)))
+                    .collect(),
+            }),
+            Object::PE(pe) => Some(AddressMap {
+                segments: pe
+                    .sections
+                    .iter()
+                    .map(|s| {
+                        let address = pe.image_base + u64::from(s.virtual_address);
+                        (address, u64::from(s.size_of_raw_data), u64::from(s.pointer_to_raw_data))
+                    })
+                    .collect(),
+                pointer_size: if pe.is_64 { 8 } else { 4 },
+                little_endian: true,
+                relocated: HashMap::new(),
+            }),
+            Object::Mach(goblin::mach::Mach::Binary(macho)) => Some(AddressMap {
+                segments: macho.segments.iter().map(|s| (s.vmaddr, s.filesize, s.fileoff)).collect(),
+                pointer_size: if macho.is_64 { 8 } else { 4 },
+                little_endian: macho.little_endian,
+                relocated: HashMap::new(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn file_offset(&self, address: u64, len: u64) -> Option<usize> {
+        self.segments.iter().find_map(|&(start, size, offset)| {
+            let delta = address.checked_sub(start)?;
+            (delta.checked_add(len)? <= size).then(|| usize::try_from(offset + delta).ok()).flatten()
+        })
+    }
+
+    fn read(&self, content: &[u8], offset: usize, size: usize) -> Option<u64> {
+        let bytes = content.get(offset..offset.checked_add(size)?)?;
+        let mut value = 0u64;
+        for i in 0..size {
+            let byte = if self.little_endian { bytes[size - 1 - i] } else { bytes[i] };
+            value = (value << 8) | u64::from(byte);
+        }
+        Some(value)
+    }
+}
+
+/// Source files named by panic `Location` records: a `&str` pointer and
+/// length followed by `u32` line and column, pointing at a `.rs` path.
+fn panic_location_files(content: &[u8]) -> HashSet<String> {
+    let mut files = HashSet::new();
+    let Some(map) = AddressMap::parse(content) else { return files };
+    let width = map.pointer_size;
+    for &(start, size, offset) in &map.segments {
+        let Ok(offset) = usize::try_from(offset) else { continue };
+        let end = usize::try_from(size).map_or(content.len(), |size| offset.saturating_add(size).min(content.len()));
+        for (i, record) in (offset..end.saturating_sub(2 * width + 8)).step_by(width).enumerate() {
+            let Some(mut pointer) = map.read(content, record, width) else { break };
+            if pointer == 0 {
+                pointer = map.relocated.get(&(start + (i * width) as u64)).copied().unwrap_or(0);
+            }
+            let (Some(len), Some(line), Some(col)) = (
+                map.read(content, record + width, width),
+                map.read(content, record + 2 * width, 4),
+                map.read(content, record + 2 * width + 4, 4),
+            ) else {
+                break;
+            };
+            if !(4..=MAX_LOCATION_PATH_LEN).contains(&len)
+                || !(1..=u64::from(MAX_LOCATION_LINE)).contains(&line)
+                || !(1..=u64::from(MAX_LOCATION_COL)).contains(&col)
+            {
+                continue;
+            }
+            let Some(file) = map.file_offset(pointer, len).and_then(|o| content.get(o..o + len as usize)) else {
+                continue;
+            };
+            if file.ends_with(b".rs") && file.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+                files.insert(String::from_utf8_lossy(file).into_owned());
+            }
+        }
+    }
+    debug!("Found {} source files in panic locations", files.len());
+    files
+}
+
+fn confidence(source_files: usize, symbols_seen: bool, panic_locations_seen: bool) -> f64 {
+    // A registry path on its own is weak evidence; it may be a stray string.
+    let mut score = 0.3;
+    score += 0.4 * ((1.0 + source_files as f64).ln() / (1.0 + SOURCE_FILE_SATURATION).ln()).min(1.0);
+    if symbols_seen {
+        score += 0.2;
+    }
+    if panic_locations_seen {
+        score += 0.1;
+    }
+    (score.min(1.0) * 100.0).round() / 100.0
+}
+
+pub fn collect_evidence(
+    content: &[u8],
+    package_offsets: &HashMap<Package, Vec<usize>>,
+    framework_paths: &HashSet<String>,
+    user_paths: &HashSet<String>,
+) -> Result<Vec<PackageEvidence>, Box<dyn std::error::Error>> {
+    let symbol_crates = symbol_crates(content)?;
+    let location_files = panic_location_files(content);
+
+    let mut evidence: Vec<PackageEvidence> = package_offsets
+        .iter()
+        .map(|(package, offsets)| {
+            let source_files = framework_paths
+                .iter()
+                .chain(user_paths.iter())
+                .filter(|path| package.contains_source_path(path))
+                .count();
+            let (name, _) = package.crate_name_and_version();
+            let symbols_seen = symbol_crates.contains(&name.replace('-', "_"));
+            let panic_locations_seen = location_files.iter().any(|file| package.contains_source_path(file));
+            let mut offsets = offsets.clone();
+            offsets.sort_unstable();
+            PackageEvidence {
+                path: package.path.clone(),
+                name: package.name.clone(),
+                version: package.version.clone(),
+                source_files,
+                confidence: confidence(source_files, symbols_seen, panic_locations_seen),
+                offsets,
+                symbols_seen,
+                panic_locations_seen,
+            }
+        })
+        .collect();
+
+    evidence.sort_by(|a, b| (&a.name, &a.version, &a.path).cmp(&(&b.name, &b.version, &b.path)));
+    Ok(evidence)
+}
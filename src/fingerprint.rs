@@ -0,0 +1,76 @@
+use std::collections::BTreeSet;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::{to_hex, Package, STD_DEPENDENCY_CRATES};
+
+/// imphash-style fingerprint of a binary's dependency set.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CrateFingerprint {
+    /// Normalized, sorted crate names.
+    pub crates: Vec<String>,
+    /// Normalized, sorted `name@version` entries.
+    pub versioned_crates: Vec<String>,
+    /// SHA-256 over `crates`; stable across dependency version bumps.
+    pub crate_hash: String,
+    /// SHA-256 over `versioned_crates`; identical dependency graphs only.
+    pub versioned_hash: String,
+}
+
+/// Crate names are case-insensitive on crates.io and `-`/`_` are
+/// interchangeable in the index, so fold both before hashing.
+fn normalize_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace('_', "-")
+}
+
+fn hash_list(items: &[String]) -> String {
+    to_hex(&Sha256::digest(items.join(",").as_bytes()))
+}
+
+/// Build the fingerprint. Crates that std itself links are left out, as
+/// they track the toolchain rather than the program.
+pub fn crate_fingerprint(packages: &[Package]) -> CrateFingerprint {
+    let mut crates = BTreeSet::new();
+    let mut versioned = BTreeSet::new();
+    for package in packages {
+        let (name, version) = package.crate_name_and_version();
+        if STD_DEPENDENCY_CRATES.contains(&name.as_str()) {
+            continue;
+        }
+        let name = normalize_name(&name);
+        versioned.insert(match version {
+            Some(version) => format!("{}@{}", name, version),
+            None => name.clone(),
+        });
+        crates.insert(name);
+    }
+
+    let crates: Vec<String> = crates.into_iter().collect();
+    let versioned_crates: Vec<String> = versioned.into_iter().collect();
+    CrateFingerprint {
+        crate_hash: hash_list(&crates),
+        versioned_hash: hash_list(&versioned_crates),
+        crates,
+        versioned_crates,
+    }
+}
+
+/// Jaccard index of two sets; two empty sets are considered identical.
+pub fn jaccard(a: &[String], b: &[String]) -> f64 {
+    let a: BTreeSet<&String> = a.iter().collect();
+    let b: BTreeSet<&String> = b.iter().collect();
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// Similarity of two dependency sets by crate name, ignoring versions.
+pub fn crate_similarity(a: &CrateFingerprint, b: &CrateFingerprint) -> f64 {
+    jaccard(&a.crates, &b.crates)
+}
+
+/// Similarity of two dependency sets including exact versions.
+pub fn versioned_similarity(a: &CrateFingerprint, b: &CrateFingerprint) -> f64 {
+    jaccard(&a.versioned_crates, &b.versioned_crates)
+}
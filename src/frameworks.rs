@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+use log::debug;
+use crate::{find_needles, Package};
+use crate::std_api::std_relative_path;
+
+/// Allocator, async runtime, web, RPC and GUI indicators, maintained in `frameworks.json`.
+const FRAMEWORK_RULES: &str = include_str!("../frameworks.json");
+
+#[derive(Deserialize, Debug)]
+struct FrameworkRule {
+    name: String,
+    category: String,
+    /// Only reported when nothing else in the category is, e.g. the system allocator.
+    #[serde(default)]
+    fallback: bool,
+    #[serde(default)]
+    crates: Vec<String>,
+    #[serde(default)]
+    std_paths: Vec<String>,
+    #[serde(default)]
+    strings: Vec<String>,
+    #[serde(default)]
+    variants: Vec<VariantRule>,
+}
+
+#[derive(Deserialize, Debug)]
+struct VariantRule {
+    name: String,
+    strings: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Framework {
+    pub name: String,
+    /// `allocator`, `async_runtime`, `web`, `rpc` or `gui`.
+    pub category: String,
+    /// Configurations compiled in, e.g. tokio's `multi_thread` and `current_thread` schedulers.
+    pub variants: Vec<String>,
+    /// Indicators that triggered it, e.g. `crate:tokio`, `std:std/src/alloc.rs` or `string:<jemalloc>: `.
+    pub evidence: Vec<String>,
+}
+
+/// The embedded table, parsed on first use.
+fn load_rules() -> Result<&'static [FrameworkRule], Box<dyn std::error::Error>> {
+    static RULES: OnceLock<Result<Vec<FrameworkRule>, String>> = OnceLock::new();
+    RULES
+        .get_or_init(|| serde_json::from_str(FRAMEWORK_RULES).map_err(|e| e.to_string()))
+        .as_deref()
+        .map_err(|e| format!("invalid embedded frameworks.json: {}", e).into())
+}
+
+/// Every rule and variant string in `content`, with either path separator.
+fn find_strings(content: &[u8], rules: &[FrameworkRule]) -> HashSet<String> {
+    let strings: Vec<&String> = rules
+        .iter()
+        .flat_map(|r| r.strings.iter().chain(r.variants.iter().flat_map(|v| &v.strings)))
+        .collect();
+    let backslashed: Vec<String> = strings.iter().map(|s| s.replace('/', "\\")).collect();
+    let needles: Vec<&[u8]> = strings.iter().map(|s| s.as_bytes()).chain(backslashed.iter().map(|s| s.as_bytes())).collect();
+    let found = find_needles(content, &needles);
+    strings
+        .into_iter()
+        .zip(&backslashed)
+        .filter(|(s, b)| found.contains(s.as_bytes()) || found.contains(b.as_bytes()))
+        .map(|(s, _)| s.clone())
+        .collect()
+}
+
+pub fn detect_frameworks<'a>(
+    content: &[u8],
+    packages: &[Package],
+    framework_paths: impl IntoIterator<Item = &'a String>,
+) -> Result<Vec<Framework>, Box<dyn std::error::Error>> {
+    let rules = load_rules()?;
+    let found_strings = find_strings(content, rules);
+    let std_paths: Vec<String> = framework_paths.into_iter().map(|p| std_relative_path(p)).collect();
+    let crate_names: HashSet<String> = packages.iter().map(|p| p.crate_name_and_version().0).collect();
+    let is_rust = !std_paths.is_empty() || !packages.is_empty();
+
+    let mut found: Vec<Framework> = Vec::new();
+    for rule in rules.iter().filter(|r| !r.fallback).chain(rules.iter().filter(|r| r.fallback)) {
+        if rule.fallback && found.iter().any(|f| f.category == rule.category) {
+            continue;
+        }
+        let mut evidence: Vec<String> = Vec::new();
+        for name in &rule.crates {
+            if crate_names.contains(name) {
+                evidence.push(format!("crate:{}", name));
+            }
+        }
+        for path in &std_paths {
+            if rule.std_paths.iter().any(|p| path.contains(p.as_str())) {
+                evidence.push(format!("std:{}", path));
+            }
+        }
+        for string in &rule.strings {
+            if found_strings.contains(string) {
+                evidence.push(format!("string:{}", string));
+            }
+        }
+        if evidence.is_empty() && rule.fallback && is_rust {
+            evidence.push("default".to_string());
+        }
+        if evidence.is_empty() {
+            continue;
+        }
+        evidence.sort();
+        evidence.dedup();
+        let variants = rule
+            .variants
+            .iter()
+            .filter(|v| v.strings.iter().any(|s| found_strings.contains(s)))
+            .map(|v| v.name.clone())
+            .collect();
+        found.push(Framework { name: rule.name.clone(), category: rule.category.clone(), variants, evidence });
+    }
+
+    found.sort_by(|a, b| a.category.cmp(&b.category).then(a.name.cmp(&b.name)));
+    debug!("Detected {} frameworks", found.len());
+    Ok(found)
+}
+
+/// Drop `crate:` evidence of packages no longer in `packages`, and frameworks
+/// left without evidence. Std path and string evidence stays, since the binary
+/// content is not at hand; a category emptied this way gets its fallback back.
+pub fn retain_packages(frameworks: &mut Vec<Framework>, packages: &[Package]) -> Result<(), Box<dyn std::error::Error>> {
+    let crate_names: HashSet<String> = packages.iter().map(|p| p.crate_name_and_version().0).collect();
+    let categories: HashSet<String> = frameworks.iter().map(|f| f.category.clone()).collect();
+    for framework in frameworks.iter_mut() {
+        framework.evidence.retain(|e| !e.starts_with("crate:") || crate_names.contains(&e["crate:".len()..]));
+    }
+    frameworks.retain(|f| !f.evidence.is_empty());
+
+    for rule in load_rules()?.iter().filter(|r| r.fallback) {
+        if categories.contains(&rule.category) && !frameworks.iter().any(|f| f.category == rule.category) {
+            frameworks.push(Framework {
+                name: rule.name.clone(),
+                category: rule.category.clone(),
+                variants: Vec::new(),
+                evidence: vec!["default".to_string()],
+            });
+        }
+    }
+    frameworks.sort_by(|a, b| a.category.cmp(&b.category).then(a.name.cmp(&b.name)));
+    Ok(())
+}
@@ -1,57 +1,251 @@
+use aho_corasick::AhoCorasick;
 use regex::bytes::Regex;
 use std::collections::{HashMap, HashSet};
 use std::fs;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::from_str;
-use log::{warn, debug};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use log::{debug, warn};
 
+pub mod baseline;
+pub mod build_env;
+pub mod build_profile;
+pub mod capabilities;
+pub mod cluster;
+pub mod crate_groups;
+pub mod cyclonedx;
+pub mod diff;
+pub mod evidence;
+pub mod fingerprint;
+pub mod frameworks;
+pub mod ownership;
 pub mod pe_strings;
+pub mod producers;
+pub mod rules;
+pub mod scaffold;
+pub mod spdx;
+pub mod std_api;
+pub mod target;
+pub mod toolchain_evidence;
 pub mod update;
+pub mod verify;
+pub mod version_estimate;
+pub mod versions;
+pub mod yara;
 
-#[derive(PartialEq, Eq, Hash, Serialize, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, Serialize, Deserialize, Debug, Clone)]
 pub struct Package {
     pub path: String,
     pub name: String,
     pub version: String
 }
 
+impl Package {
+    /// Crate name and version with the unversioned-directory split undone:
+    /// a registry directory like `base64` is captured as `base` / `64`, which
+    /// is reported here as crate `base64` with an unknown version.
+    pub fn crate_name_and_version(&self) -> (String, Option<String>) {
+        if self.version.contains('.') {
+            (self.name.clone(), Some(self.version.clone()))
+        } else {
+            (format!("{}{}", self.name, self.version), None)
+        }
+    }
+
+    /// Package URL identifying this crate on crates.io.
+    pub fn purl(&self) -> String {
+        match self.crate_name_and_version() {
+            (name, Some(version)) => format!("pkg:cargo/{}@{}", name, version),
+            (name, None) => format!("pkg:cargo/{}", name),
+        }
+    }
+
+    /// Whether `source_path` lies under this package's registry directory,
+    /// regardless of the path separator used by the build host.
+    pub fn contains_source_path(&self, source_path: &str) -> bool {
+        let dir = format!("{}/", self.path.replace('\\', "/"));
+        source_path.replace('\\', "/").contains(&dir)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FileInfo {
+    pub name: String,
+    pub size: u64,
+    pub sha1: String,
+    pub sha256: String,
+}
+
+impl FileInfo {
+    fn from_content(file_path: &str, content: &[u8]) -> Self {
+        let name = std::path::Path::new(file_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| file_path.to_string());
+        FileInfo {
+            name,
+            size: content.len() as u64,
+            sha1: to_hex(&Sha1::digest(content)),
+            sha256: to_hex(&Sha256::digest(content)),
+        }
+    }
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The `needles` that occur in `content`, found in a single pass over it.
+pub(crate) fn find_needles<'a>(content: &[u8], needles: &[&'a [u8]]) -> HashSet<&'a [u8]> {
+    let mut found = HashSet::new();
+    match AhoCorasick::new(needles) {
+        Ok(searcher) => {
+            for mat in searcher.find_overlapping_iter(content) {
+                found.insert(needles[mat.pattern().as_usize()]);
+            }
+        }
+        Err(e) => {
+            warn!("Falling back to a slow substring search: {}", e);
+            found.extend(needles.iter().filter(|n| content.windows(n.len()).any(|w| w == **n)));
+        }
+    }
+    found
+}
+
 pub const DEFAULT_MIN_STR_LEN: usize = 4;
 
-#[derive(Serialize, Debug, Clone)]
+/// Crates the standard library itself depends on. They show up in registry
+/// paths of most binaries regardless of the program's own dependencies.
+pub const STD_DEPENDENCY_CRATES: &[&str] = &[
+    "addr2line",
+    "adler",
+    "adler2",
+    "cc",
+    "cfg-if",
+    "compiler_builtins",
+    "dlmalloc",
+    "fortanix-sgx-abi",
+    "getopts",
+    "gimli",
+    "hashbrown",
+    "hermit-abi",
+    "libc",
+    "memchr",
+    "miniz_oxide",
+    "object",
+    "r-efi",
+    "r-efi-alloc",
+    "rustc-demangle",
+    "unicode-width",
+    "unwinding",
+    "wasi",
+];
+
+/// Fields added after the first report format are `#[serde(default)]` so
+/// older saved JSON reports still load.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AnalysisResult {
+    #[serde(default)]
+    pub file: FileInfo,
     pub packages: Vec<Package>,
+    #[serde(default)]
+    pub package_evidence: Vec<evidence::PackageEvidence>,
+    #[serde(default)]
+    pub crate_groups: crate_groups::CrateGroupReport,
+    #[serde(default)]
+    pub crate_fingerprint: fingerprint::CrateFingerprint,
     pub framework_source_paths: HashSet<String>,
     pub user_source_paths: HashSet<String>,
     pub rustc_hash: Option<String>,
+    /// Every known commit an abbreviated `rustc_hash` could be, when it is ambiguous.
+    #[serde(default)]
+    pub rustc_hash_candidates: Vec<String>,
     pub rust_version: Option<String>,
+    /// LLVM version named by an LLD, LLVM or clang producer record.
+    #[serde(default)]
+    pub llvm_version: Option<String>,
+    /// `.comment`, DWARF producer and WebAssembly `producers` records.
+    #[serde(default)]
+    pub producers: Vec<producers::Producer>,
+    /// Channel, date, LLVM version and tag of the resolved toolchain.
+    #[serde(default)]
+    pub toolchain: Option<versions::ToolchainInfo>,
+    /// Version range inferred from std layout, panic format and crate MSRVs
+    /// when `rust_version` could not be resolved.
+    #[serde(default)]
+    pub version_estimate: Option<version_estimate::VersionEstimate>,
+    /// Every rustc hash and toolchain directory seen, and build-std detection.
+    #[serde(default)]
+    pub toolchain_evidence: toolchain_evidence::ToolchainEvidence,
+    /// Target triple inferred from the container, std modules and C runtime artefacts.
+    #[serde(default)]
+    pub target: target::TargetInfo,
+    /// Debug or release profile, panic strategy, debuginfo, stripping and LTO.
+    #[serde(default)]
+    pub build_profile: build_profile::BuildProfile,
+    /// CI system, container, build host OS and cargo home.
+    #[serde(default)]
+    pub build_environment: build_env::BuildEnvironment,
     pub language_strings: Vec<pe_strings::ExtractedString>,
+    #[serde(default)]
+    pub capabilities: Vec<capabilities::Capability>,
+    /// Global allocator, async runtime and web, RPC or GUI frameworks.
+    #[serde(default)]
+    pub frameworks: Vec<frameworks::Framework>,
+    #[serde(default)]
+    pub std_modules: Vec<std_api::StdModule>,
+    #[serde(default)]
+    pub rule_matches: Vec<rules::RuleMatch>,
 }
 
-pub fn load_version_mappings() -> Option<HashMap<String, String>> {
-    match fs::read_to_string("rust_versions.json") {
-        Ok(content) => {
-            match from_str::<HashMap<String, String>>(&content) {
-                Ok(mappings) => Some(mappings),
-                Err(e) => {
-                    warn!("Failed to parse rust_versions.json: {}", e);
-                    None
-                }
-            }
+impl AnalysisResult {
+    /// Drop packages whose evidence confidence is below `min_confidence`.
+    /// Reports saved before evidence scoring carry none and are left as is.
+    pub fn retain_min_confidence(&mut self, min_confidence: f64) {
+        if self.package_evidence.is_empty() && !self.packages.is_empty() {
+            warn!("{} has no package evidence, ignoring the confidence filter", self.file.name);
+            return;
         }
-        Err(e) => {
-            warn!("Failed to read rust_versions.json: {}", e);
-            None
+        self.package_evidence.retain(|e| e.confidence >= min_confidence);
+        let kept: HashSet<&str> = self.package_evidence.iter().map(|e| e.path.as_str()).collect();
+        self.packages.retain(|p| kept.contains(p.path.as_str()));
+        self.crate_groups = crate_groups::group_packages(
+            &self.packages,
+            &self.framework_source_paths,
+            &self.user_source_paths,
+        );
+        self.crate_fingerprint = fingerprint::crate_fingerprint(&self.packages);
+        match capabilities::infer_capabilities(&self.packages, &self.framework_source_paths) {
+            Ok(capabilities) => self.capabilities = capabilities,
+            Err(e) => warn!("Keeping capabilities of {}: {}", self.file.name, e),
+        }
+        if let Err(e) = frameworks::retain_packages(&mut self.frameworks, &self.packages) {
+            warn!("Keeping frameworks of {}: {}", self.file.name, e);
         }
     }
+
+    /// Drop language strings the baseline attributes to std or a dependency.
+    pub fn retain_unique_strings(&mut self) {
+        self.language_strings.retain(|s| !s.known_library);
+    }
 }
 
-fn extract_packages(content: &[u8]) -> Result<HashSet<Package>, Box<dyn std::error::Error>> {
+/// Commit hash to version string. Reads either the flat or the versioned
+/// `rust_versions.json`; see `versions::load_version_database` for metadata.
+pub fn load_version_mappings() -> Option<HashMap<String, String>> {
+    versions::load_version_database().map(|database| database.mappings())
+}
+
+/// Extract packages along with the file offsets of every registry path
+/// match that produced them.
+fn extract_packages(content: &[u8]) -> Result<HashMap<Package, Vec<usize>>, Box<dyn std::error::Error>> {
     // Keep the original more permissive regex pattern to handle edge cases like "base64"
     let re = Regex::new(
         r".cargo(?:/|\\)registry(?:/|\\)src(?:/|\\).*?-[a-f0-9]{8,}(?:/|\\)(.*?)-?([\d\.]{2,})"
     )?;
 
-    let mut packages = HashSet::new();
+    let mut packages: HashMap<Package, Vec<usize>> = HashMap::new();
     for mat in re.captures_iter(content) {
         if let (Some(path_match), Some(name_match), Some(version_match)) = 
             (mat.get(0), mat.get(1), mat.get(2)) {
@@ -65,32 +259,36 @@ fn extract_packages(content: &[u8]) -> Result<HashSet<Package>, Box<dyn std::err
                 name: name_str.to_string(),
                 version: version_str.to_string(),
             };
-            packages.insert(package);
+            packages.entry(package).or_default().push(path_match.start());
         }
     }
     debug!("Extracted {} packages", packages.len());
     Ok(packages)
 }
 
-fn extract_rustc_info(content: &[u8]) -> Result<Option<String>, Box<dyn std::error::Error>> {
-    let rustc_hash_re = Regex::new(r"/rustc/([a-f0-9]{40})")?;
-    let path_re = Regex::new(
-        r"(?:[a-zA-Z]:[\\/]|/)(?:[a-zA-Z0-9._\-]+[\\/]){1,512}?(?:[a-zA-Z0-9._\-]+)\.rs"
+/// The rustc commit hash, in full from the most common `/rustc/<hash>/` source
+/// path, or abbreviated from version strings such as the ELF `.comment` entry
+/// `rustc version 1.75.0 (82e1608df 2023-12-21)`, which also give the version.
+fn extract_rustc_info(
+    content: &[u8],
+    evidence: &toolchain_evidence::ToolchainEvidence,
+) -> Result<(Option<String>, Option<String>), Box<dyn std::error::Error>> {
+    if let Some(hash) = evidence.primary_hash() {
+        debug!("Found rustc hash: {}", hash);
+        return Ok((Some(hash.to_string()), None));
+    }
+
+    let version_re = Regex::new(
+        r"rustc (?:version )?(\d+\.\d+\.\d+(?:-(?:beta(?:\.\d+)?|nightly|dev))?) \(([a-f0-9]{7,40}) \d{4}-\d{2}-\d{2}\)"
     )?;
-    
-    for mat in path_re.find_iter(content) {
-        if let Ok(path_str) = std::str::from_utf8(mat.as_bytes()) {
-            if let Some(hash_match) = rustc_hash_re.captures(path_str.as_bytes()) {
-                if let Some(hash_capture) = hash_match.get(1) {
-                    if let Ok(hash_str) = std::str::from_utf8(hash_capture.as_bytes()) {
-                        debug!("Found rustc hash: {}", hash_str);
-                        return Ok(Some(hash_str.to_string()));
-                    }
-                }
-            }
-        }
+    if let Some(caps) = version_re.captures(content) {
+        let (Some(version), Some(hash)) = (caps.get(1), caps.get(2)) else { return Ok((None, None)) };
+        let version = String::from_utf8_lossy(version.as_bytes()).into_owned();
+        let hash = String::from_utf8_lossy(hash.as_bytes()).into_owned();
+        debug!("Found rustc version string: {} ({})", version, hash);
+        return Ok((Some(hash), Some(version)));
     }
-    Ok(None)
+    Ok((None, None))
 }
 
 fn categorize_paths(content: &[u8]) -> Result<(HashSet<String>, HashSet<String>), Box<dyn std::error::Error>> {
@@ -118,11 +316,10 @@ fn categorize_paths(content: &[u8]) -> Result<(HashSet<String>, HashSet<String>)
 
 fn is_framework_path(path: &str) -> bool {
     path.starts_with("/rust") 
-        || path.contains(".cargo")
+        || build_env::is_cargo_home_path(path)
+        || path.starts_with("/root/")
         || path.contains(".rustup")
         || path.contains(".crates.io")
-        || path.starts_with("/root/")
-        || path.starts_with("/cargo/")
         || path.starts_with("/core/") 
         || path.starts_with("/std/") 
         || path.starts_with("/alloc/") 
@@ -131,12 +328,44 @@ fn is_framework_path(path: &str) -> bool {
         || path.starts_with("/test/")
 }
 
-fn resolve_rust_version(rustc_hash: &Option<String>, version_mappings: &Option<HashMap<String, String>>) -> Option<String> {
-    if let (Some(hash), Some(mappings)) = (rustc_hash, version_mappings) {
-        mappings.get(hash).cloned()
-    } else {
-        None
+struct ResolvedToolchain {
+    /// The full hash once an abbreviated one is resolved.
+    hash: Option<String>,
+    toolchain: Option<versions::ToolchainInfo>,
+    candidates: Vec<String>,
+}
+
+/// Look the hash up by unique prefix. When a prefix is ambiguous, a version
+/// embedded next to it can still single out one commit.
+fn resolve_rust_version(
+    rustc_hash: &Option<String>,
+    embedded_version: &Option<String>,
+    database: &Option<versions::VersionDatabase>,
+) -> ResolvedToolchain {
+    let mut resolved = ResolvedToolchain { hash: rustc_hash.clone(), toolchain: None, candidates: Vec::new() };
+    let (Some(hash), Some(database)) = (rustc_hash, database) else { return resolved };
+    match database.lookup(hash) {
+        versions::HashLookup::Found(full, info) => {
+            resolved.hash = Some(full.to_string());
+            resolved.toolchain = Some(info.clone());
+        }
+        versions::HashLookup::Ambiguous(candidates) => {
+            let matching: Vec<&str> = candidates
+                .iter()
+                .copied()
+                .filter(|c| database.get(c).map(|info| &info.version) == embedded_version.as_ref())
+                .collect();
+            if let [full] = matching.as_slice() {
+                resolved.hash = Some(full.to_string());
+                resolved.toolchain = database.get(full).cloned();
+            } else {
+                warn!("rustc hash {} is ambiguous: matches {}", hash, candidates.join(", "));
+                resolved.candidates = candidates.iter().map(|c| c.to_string()).collect();
+            }
+        }
+        versions::HashLookup::NotFound => {}
     }
+    resolved
 }
 
 pub fn analyze_binary(file_path: &str, min_length: usize) -> Result<AnalysisResult, Box<dyn std::error::Error>> {
@@ -145,16 +374,45 @@ pub fn analyze_binary(file_path: &str, min_length: usize) -> Result<AnalysisResu
     let content = fs::read(file_path)?;
     debug!("Read {} bytes from binary", content.len());
     
-    let version_mappings = load_version_mappings();
-    let packages = extract_packages(&content)?;
-    let rustc_hash = extract_rustc_info(&content)?;
+    let version_database = versions::load_version_database();
+    let package_offsets = extract_packages(&content)?;
+    let toolchain_evidence = toolchain_evidence::collect_toolchain_evidence(&content)?;
+    let (mut rustc_hash, mut embedded_version) = extract_rustc_info(&content, &toolchain_evidence)?;
+    let producer_records = producers::extract_producers(&content)?;
+    if let Some(rustc) = producers::rustc_version(&producer_records)? {
+        rustc_hash = rustc_hash.or(rustc.hash);
+        embedded_version = Some(rustc.version);
+    }
+    embedded_version = embedded_version.or_else(|| toolchain_evidence.toolchain_dir_version());
+    let llvm_version = producers::llvm_version(&producer_records)?;
     let (framework_paths, user_paths) = categorize_paths(&content)?;
-    let rust_version = resolve_rust_version(&rustc_hash, &version_mappings);
-    let packages_vec: Vec<Package> = packages.into_iter().collect();
+    let ResolvedToolchain { hash: rustc_hash, toolchain, candidates: rustc_hash_candidates } =
+        resolve_rust_version(&rustc_hash, &embedded_version, &version_database);
+    let rust_version = toolchain.as_ref().map(|t| t.version.clone()).or(embedded_version);
+    let package_evidence = evidence::collect_evidence(&content, &package_offsets, &framework_paths, &user_paths)?;
+    let packages_vec: Vec<Package> = package_offsets.into_keys().collect();
+    let version_estimate = match rust_version {
+        Some(_) => None,
+        None => version_estimate::estimate_rust_version(&content, &packages_vec)?,
+    };
+    let crate_groups = crate_groups::group_packages(&packages_vec, &framework_paths, &user_paths);
+    let crate_fingerprint = fingerprint::crate_fingerprint(&packages_vec);
+    let capabilities = capabilities::infer_capabilities(&packages_vec, &framework_paths)?;
+    let frameworks = frameworks::detect_frameworks(&content, &packages_vec, &framework_paths)?;
+    let std_modules = std_api::map_std_modules(&framework_paths);
+    let target = target::infer_target(&content, &framework_paths)?;
+    let build_profile = build_profile::detect_build_profile(&content)?;
+    let build_environment = build_env::classify_build_environment(&content)?;
 
     let language_strings = if pe_strings::is_pe(&content) {
         debug!("PE detected, extracting language strings");
-        pe_strings::extract_rust_strings(&content, min_length)
+        let mut strings = pe_strings::extract_rust_strings(&content, min_length);
+        let string_baseline = baseline::load_string_baseline();
+        if let Some(string_baseline) = &string_baseline {
+            baseline::mark_known_strings(&mut strings, string_baseline, &packages_vec);
+        }
+        ownership::attribute_owners(&mut strings, &packages_vec, string_baseline.as_ref());
+        strings
     } else {
         debug!("not a PE, skipping language string extraction");
         Vec::new()
@@ -164,11 +422,53 @@ pub fn analyze_binary(file_path: &str, min_length: usize) -> Result<AnalysisResu
            packages_vec.len(), language_strings.len(), rustc_hash, rust_version);
     
     Ok(AnalysisResult {
+        file: FileInfo::from_content(file_path, &content),
         packages: packages_vec,
+        package_evidence,
+        crate_groups,
+        crate_fingerprint,
         framework_source_paths: framework_paths,
         user_source_paths: user_paths,
         rustc_hash,
+        rustc_hash_candidates,
         rust_version,
+        llvm_version,
+        producers: producer_records,
+        toolchain,
+        version_estimate,
+        toolchain_evidence,
+        target,
+        build_profile,
+        build_environment,
         language_strings,
+        capabilities,
+        frameworks,
+        std_modules,
+        rule_matches: Vec::new(),
     })
-} 
\ No newline at end of file
+}
+
+/// Load a saved JSON report, or analyze the file as a binary otherwise.
+pub fn load_or_analyze(file_path: &str, min_length: usize) -> Result<AnalysisResult, Box<dyn std::error::Error>> {
+    let is_report = std::path::Path::new(file_path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    if !is_report {
+        return analyze_binary(file_path, min_length);
+    }
+
+    debug!("Loading saved report: {}", file_path);
+    let mut result: AnalysisResult = from_str(&fs::read_to_string(file_path)?)
+        .map_err(|e| format!("{} is not a rustre JSON report: {}", file_path, e))?;
+    // Reports written before the fingerprint existed carry an empty one.
+    if result.crate_fingerprint.crate_hash.is_empty() {
+        result.crate_fingerprint = fingerprint::crate_fingerprint(&result.packages);
+    }
+    if result.file.name.is_empty() {
+        result.file.name = std::path::Path::new(file_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| file_path.to_string());
+    }
+    Ok(result)
+}
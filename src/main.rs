@@ -1,24 +1,106 @@
+use std::path::PathBuf;
 use std::process;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use serde_json::to_string_pretty;
 use log::{error, info};
-use rustre::{analyze_binary, DEFAULT_MIN_STR_LEN};
-use rustre::update::update_rust_versions;
+use rustre::{analyze_binary, load_or_analyze, AnalysisResult, DEFAULT_MIN_STR_LEN};
+use rustre::baseline::update_string_baseline;
+use rustre::cluster::{cluster_directory, DEFAULT_CLUSTER_THRESHOLD};
+use rustre::cyclonedx::to_cyclonedx;
+use rustre::diff::diff_results;
+use rustre::rules::{evaluate_rules, load_default_rules, load_rules};
+use rustre::scaffold::scaffold_binary;
+use rustre::spdx::{to_spdx, to_tag_value};
+use rustre::update::{update_channel_builds, update_rust_versions};
+use rustre::verify::verify;
+use rustre::yara::generate_yara;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputFormat {
+    /// rustre's own analysis report
+    Json,
+    /// CycloneDX 1.5 SBOM (JSON)
+    Cyclonedx,
+    /// SPDX 2.3 SBOM (JSON)
+    SpdxJson,
+    /// SPDX 2.3 SBOM (tag-value)
+    SpdxTv,
+    /// YARA rule built from the sample's distinctive strings
+    Yara,
+}
+
+fn render(result: &AnalysisResult, format: OutputFormat) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(match format {
+        OutputFormat::Json => to_string_pretty(result)?,
+        OutputFormat::Cyclonedx => to_string_pretty(&to_cyclonedx(result))?,
+        OutputFormat::SpdxJson => to_string_pretty(&to_spdx(result))?,
+        OutputFormat::SpdxTv => to_tag_value(&to_spdx(result))?,
+        OutputFormat::Yara => generate_yara(result)?,
+    })
+}
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Path to the binary file to analyze
-    #[arg(required_unless_present = "update_versions")]
+    /// Path to the binary file to analyze (or a saved JSON report with --diff)
+    #[arg(required_unless_present_any = ["update_versions", "import_channels", "cluster", "build_baseline"])]
     file_path: Option<String>,
     
     /// Minimum string length for PE language string extraction
     #[arg(short = 'n', long = "min-length", default_value_t = DEFAULT_MIN_STR_LEN)]
     min_length: usize,
 
+    /// Drop packages whose evidence confidence (0.0-1.0) is below this value
+    #[arg(long, value_name = "SCORE")]
+    min_confidence: Option<f64>,
+
+    /// Only report language strings not found in the library string baseline
+    #[arg(long)]
+    unique_only: bool,
+
+    /// Output format for the analysis report; --diff, --verify and --cluster only write json
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = OutputFormat::Json)]
+    format: OutputFormat,
+
+    /// Evaluate family rules from this TOML file instead of rules.toml
+    #[arg(long, value_name = "FILE")]
+    rules: Option<PathBuf>,
+
+    /// Verify the binary against a Cargo.lock, CycloneDX or SPDX document
+    #[arg(long, value_name = "LOCKFILE")]
+    verify: Option<String>,
+
+    /// Write a Cargo project scaffold reproducing the binary's dependencies and toolchain
+    #[arg(long, value_name = "DIR")]
+    scaffold: Option<PathBuf>,
+
+    /// Compare against a newer binary or saved JSON report
+    #[arg(long, value_name = "OTHER")]
+    diff: Option<String>,
+
+    /// Cluster every binary or saved JSON report in a directory
+    #[arg(long, value_name = "DIR", conflicts_with = "file_path")]
+    cluster: Option<PathBuf>,
+
+    /// Similarity (0.0-1.0) at which two samples are linked when clustering
+    #[arg(long, value_name = "SCORE", default_value_t = DEFAULT_CLUSTER_THRESHOLD)]
+    cluster_threshold: f64,
+
+    /// Add strings of the reference binaries in a directory to string_baseline.json
+    #[arg(long, value_name = "DIR", conflicts_with = "file_path")]
+    build_baseline: Option<PathBuf>,
+
     /// Update the Rust versions database from GitHub
     #[arg(long, help = "Update the Rust versions database from GitHub API")]
     update_versions: bool,
+
+    /// Import nightly and beta builds from a dist mirror directory or URL into rust_versions.json
+    #[arg(long, value_name = "SOURCE", conflicts_with = "file_path")]
+    import_channels: Option<String>,
+
+    /// With a URL --import-channels, also fetch dated manifests from this day (YYYY-MM-DD) on
+    #[arg(long, value_name = "DATE", requires = "import_channels")]
+    channel_since: Option<String>,
 }
 
 #[tokio::main]
@@ -28,6 +110,11 @@ async fn main() {
     
     let args = Args::parse();
     
+    if (args.diff.is_some() || args.verify.is_some() || args.cluster.is_some()) && !matches!(args.format, OutputFormat::Json) {
+        error!("--diff, --verify and --cluster only write json; drop --format");
+        process::exit(1);
+    }
+
     if args.update_versions {
         // Update the Rust versions database
         match update_rust_versions().await {
@@ -39,17 +126,121 @@ async fn main() {
                 process::exit(1);
             }
         }
+    } else if let Some(source) = args.import_channels {
+        match update_channel_builds(&source, args.channel_since.as_deref()).await {
+            Ok(count) => info!("Imported {} channel builds from {}", count, source),
+            Err(e) => {
+                error!("Error importing channel manifests from {}: {}", source, e);
+                process::exit(1);
+            }
+        }
+    } else if let Some(dir) = args.build_baseline {
+        match update_string_baseline(&dir, args.min_length) {
+            Ok(added) => info!("Attributed {} strings from {}", added, dir.display()),
+            Err(e) => {
+                error!("Error building string baseline from {}: {}", dir.display(), e);
+                process::exit(1);
+            }
+        }
+    } else if let Some(dir) = args.cluster {
+        match cluster_directory(&dir, args.min_length, args.cluster_threshold) {
+            Ok(report) => match to_string_pretty(&report) {
+                Ok(json) => println!("{}", json),
+                Err(e) => {
+                    error!("Error serializing report: {}", e);
+                    process::exit(1);
+                }
+            },
+            Err(e) => {
+                error!("Error clustering {}: {}", dir.display(), e);
+                process::exit(1);
+            }
+        }
+    } else if let (Some(old_path), Some(new_path)) = (&args.file_path, &args.diff) {
+        let load = |path: &str| {
+            load_or_analyze(path, args.min_length).map(|mut result| {
+                if let Some(min_confidence) = args.min_confidence {
+                    result.retain_min_confidence(min_confidence);
+                }
+                if args.unique_only {
+                    result.retain_unique_strings();
+                }
+                result
+            })
+        };
+        match (load(old_path), load(new_path)) {
+            (Ok(old), Ok(new)) => match to_string_pretty(&diff_results(&old, &new)) {
+                Ok(json) => println!("{}", json),
+                Err(e) => {
+                    error!("Error serializing report: {}", e);
+                    process::exit(1);
+                }
+            },
+            (Err(e), _) | (_, Err(e)) => {
+                error!("Error analyzing binary: {}", e);
+                process::exit(1);
+            }
+        }
     } else if let Some(file_path) = args.file_path {
+        let rules = match args.rules.as_deref().map(load_rules).transpose() {
+            Ok(rules) => rules.or_else(load_default_rules),
+            Err(e) => {
+                error!("Error loading rules: {}", e);
+                process::exit(1);
+            }
+        };
         // Analyze the binary file
-        match analyze_binary(&file_path, args.min_length) {
-            Ok(result) => {
-                if let Ok(json) = to_string_pretty(&result) {
-                    println!("{}", json);
-                } else {
-                    error!("Error serializing to JSON");
+        let analysis = analyze_binary(&file_path, args.min_length).map(|mut result| {
+            if let Some(min_confidence) = args.min_confidence {
+                result.retain_min_confidence(min_confidence);
+            }
+            if args.unique_only {
+                result.retain_unique_strings();
+            }
+            // Rules see the report as filtered.
+            if let Some(rules) = &rules {
+                result.rule_matches = evaluate_rules(rules, &result);
+            }
+            result
+        });
+        match analysis {
+            Ok(result) if args.verify.is_some() => {
+                let declared_path = args.verify.unwrap_or_default();
+                match verify(&result, &declared_path) {
+                    Ok(report) => {
+                        match to_string_pretty(&report) {
+                            Ok(json) => println!("{}", json),
+                            Err(e) => {
+                                error!("Error serializing report: {}", e);
+                                process::exit(1);
+                            }
+                        }
+                        if !report.passed {
+                            process::exit(2);
+                        }
+                    }
+                    Err(e) => {
+                        error!("Error verifying against {}: {}", declared_path, e);
+                        process::exit(1);
+                    }
+                }
+            }
+            Ok(result) if args.scaffold.is_some() => {
+                let out_dir = args.scaffold.unwrap_or_default();
+                if let Err(e) = scaffold_binary(&file_path, &result, &out_dir) {
+                    error!("Error writing scaffold to {}: {}", out_dir.display(), e);
                     process::exit(1);
                 }
             }
+            Ok(result) => {
+                match render(&result, args.format) {
+                    Ok(output) => println!("{}", output),
+                    Err(e) => {
+                        error!("Error rendering report: {}", e);
+                        process::exit(1);
+                    }
+                }
+            }
             Err(e) => {
                 error!("Error analyzing binary: {}", e);
                 process::exit(1);
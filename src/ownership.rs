@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use log::debug;
+use crate::baseline::{linked_crates, StringBaseline};
+use crate::pe_strings::{is_std_location, ExtractedString};
+use crate::{Package, STD_DEPENDENCY_CRATES};
+
+/// Owner of strings written in the binary's own, non-registry crates.
+pub const USER_OWNER: &str = "<user>";
+
+/// Crates shipped with the toolchain; their strings are all reported as `std`.
+const SYSROOT_CRATES: &[&str] = &["std", "core", "alloc", "panic_unwind", "panic_abort", "proc_macro", "test"];
+
+/// Crate name to its linked packages, names folded the way crates.io does.
+fn packages_by_name(packages: &[Package]) -> HashMap<String, Vec<&Package>> {
+    let mut by_name: HashMap<String, Vec<&Package>> = HashMap::new();
+    for package in packages {
+        let (name, _) = package.crate_name_and_version();
+        by_name.entry(name.to_ascii_lowercase().replace('_', "-")).or_default().push(package);
+    }
+    by_name
+}
+
+fn label(package: &Package) -> String {
+    match package.crate_name_and_version() {
+        (name, Some(version)) => format!("{}@{}", name, version),
+        (name, None) => name,
+    }
+}
+
+/// `name@version` when the binary links exactly one version of the crate.
+fn crate_owner(name: &str, by_name: &HashMap<String, Vec<&Package>>) -> String {
+    let key = name.to_ascii_lowercase().replace('_', "-");
+    if SYSROOT_CRATES.contains(&key.as_str()) || STD_DEPENDENCY_CRATES.contains(&key.as_str()) {
+        return "std".to_string();
+    }
+    match by_name.get(&key).map(Vec::as_slice) {
+        Some([package]) => label(package),
+        _ => name.to_string(),
+    }
+}
+
+/// Crate root of a legacy `_ZN` or v0 `_R` mangled name.
+fn symbol_root(symbol: &str) -> Option<&str> {
+    let rest = if let Some(start) = symbol.find("_ZN") {
+        &symbol[start + 3..]
+    } else if symbol.trim_start_matches('_').starts_with('R') {
+        // v0: the first `C` crate root, with an optional `s<hash>_` disambiguator.
+        let start = symbol.find('C')? + 1;
+        let rest = &symbol[start..];
+        match rest.strip_prefix('s') {
+            Some(hashed) => &hashed[hashed.find('_')? + 1..],
+            None => rest,
+        }
+    } else {
+        return None;
+    };
+    let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+    let len: usize = rest[..digits].parse().ok()?;
+    let ident = rest.get(digits..digits + len)?;
+    ident.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_').then_some(ident)
+}
+
+fn symbol_owner(symbol: &str, by_name: &HashMap<String, Vec<&Package>>) -> Option<String> {
+    let root = symbol_root(symbol)?;
+    let key = root.to_ascii_lowercase().replace('_', "-");
+    if by_name.contains_key(&key) || SYSROOT_CRATES.contains(&key.as_str()) || STD_DEPENDENCY_CRATES.contains(&key.as_str()) {
+        Some(crate_owner(root, by_name))
+    } else {
+        Some(USER_OWNER.to_string())
+    }
+}
+
+fn location_owner(path: &str, packages: &[Package]) -> String {
+    if is_std_location(path) {
+        return "std".to_string();
+    }
+    match packages.iter().find(|p| p.contains_source_path(path)) {
+        Some(package) if STD_DEPENDENCY_CRATES.contains(&package.crate_name_and_version().0.as_str()) => "std".to_string(),
+        Some(package) => label(package),
+        None => USER_OWNER.to_string(),
+    }
+}
+
+/// Fill `owner` and `owner_evidence` on every string. A COFF symbol is the
+/// strongest evidence, then a baseline match among linked crates, then the
+/// nearest panic `Location`, which inlining makes the least reliable.
+pub fn attribute_owners(strings: &mut [ExtractedString], packages: &[Package], baseline: Option<&StringBaseline>) {
+    let by_name = packages_by_name(packages);
+    let linked = linked_crates(packages);
+    for s in strings.iter_mut() {
+        let (owner, evidence) = if let Some(owner) = s.symbol.as_deref().and_then(|sym| symbol_owner(sym, &by_name)) {
+            (owner, "symbol")
+        } else if let Some(name) = baseline.and_then(|b| b.owner_of(&s.string, &linked)) {
+            (crate_owner(name, &by_name), "baseline")
+        } else if let Some(path) = &s.source_path {
+            (location_owner(path, packages), "location")
+        } else {
+            continue;
+        };
+        s.owner = Some(owner);
+        s.owner_evidence = Some(evidence.to_string());
+    }
+    debug!(
+        "{} of {} language strings attributed to an owner",
+        strings.iter().filter(|s| s.owner.is_some()).count(),
+        strings.len()
+    );
+}
@@ -1,15 +1,50 @@
+use std::collections::{BTreeMap, HashMap};
 use goblin::pe::section_table::{SectionTable, IMAGE_SCN_MEM_EXECUTE, IMAGE_SCN_MEM_READ};
+use goblin::pe::symbol::IMAGE_SYM_DTYPE_FUNCTION;
 use goblin::pe::PE;
 use log::{debug, error};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 const IMAGE_FILE_MACHINE_I386: u16 = 0x14c;
 const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
 
-#[derive(Debug, Clone, Serialize)]
+/// Farthest a panic `Location` reference may be from a string's use for the
+/// string to be tied to that source file.
+const MAX_LOCATION_DISTANCE: u64 = 0x200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExtractedString {
     pub string: String,
     pub offset: usize,
+    /// Literal of std or a dependency crate according to `string_baseline.json`.
+    #[serde(default)]
+    pub known_library: bool,
+    /// Source file of the panic `Location` referenced closest to the code using the string.
+    #[serde(default)]
+    pub source_path: Option<String>,
+    /// COFF symbol of the function using the string, when the symbol table survived.
+    #[serde(default)]
+    pub symbol: Option<String>,
+    /// Best-guess owning crate, e.g. `reqwest@0.11.27`, `std` or `<user>`.
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// What the owner guess is based on: `symbol`, `location` or `baseline`.
+    #[serde(default)]
+    pub owner_evidence: Option<String>,
+}
+
+impl ExtractedString {
+    fn new(string: String, offset: usize) -> Self {
+        ExtractedString {
+            string,
+            offset,
+            known_library: false,
+            source_path: None,
+            symbol: None,
+            owner: None,
+            owner_evidence: None,
+        }
+    }
 }
 
 struct SectionInfo {
@@ -130,7 +165,7 @@ const AMD64_LEA_PREFIXES: &[[u8; 3]] = &[
     [0x4C, 0x8D, 0x3D], // lea r15,[rip+X]
 ];
 
-fn find_amd64_lea_xrefs(code: &[u8], base_addr: u64) -> Vec<u64> {
+fn find_amd64_lea_xrefs(code: &[u8], base_addr: u64) -> Vec<(u64, u64)> {
     const INSN_LEN: u64 = 7;
     let mut results = Vec::new();
     if code.len() < 7 {
@@ -144,7 +179,7 @@ fn find_amd64_lea_xrefs(code: &[u8], base_addr: u64) -> Vec<u64> {
                     .wrapping_add(i as i64)
                     .wrapping_add(offset as i64)
                     .wrapping_add(INSN_LEN as i64);
-                results.push(target as u64);
+                results.push((base_addr + i as u64, target as u64));
                 break;
             }
         }
@@ -161,7 +196,7 @@ const I386_LEA_PREFIXES: &[[u8; 2]] = &[
     [0x8D, 0x3D], // lea edi,ds:X
 ];
 
-fn find_i386_lea_xrefs(code: &[u8]) -> Vec<u64> {
+fn find_i386_lea_xrefs(code: &[u8], base_addr: u64) -> Vec<(u64, u64)> {
     let mut results = Vec::new();
     if code.len() < 6 {
         return results;
@@ -169,7 +204,7 @@ fn find_i386_lea_xrefs(code: &[u8]) -> Vec<u64> {
     for i in 0..code.len() - 5 {
         for prefix in I386_LEA_PREFIXES {
             if code[i] == prefix[0] && code[i + 1] == prefix[1] {
-                results.push(read_u32_le(code, i + 2) as u64);
+                results.push((base_addr + i as u64, read_u32_le(code, i + 2) as u64));
                 break;
             }
         }
@@ -177,14 +212,14 @@ fn find_i386_lea_xrefs(code: &[u8]) -> Vec<u64> {
     results
 }
 
-fn find_i386_push_xrefs(code: &[u8]) -> Vec<u64> {
+fn find_i386_push_xrefs(code: &[u8], base_addr: u64) -> Vec<(u64, u64)> {
     let mut results = Vec::new();
     if code.len() < 5 {
         return results;
     }
     for i in 0..code.len() - 4 {
         if code[i] == 0x68 {
-            results.push(read_u32_le(code, i + 1) as u64);
+            results.push((base_addr + i as u64, read_u32_le(code, i + 1) as u64));
         }
     }
     results
@@ -192,19 +227,20 @@ fn find_i386_push_xrefs(code: &[u8]) -> Vec<u64> {
 
 const I386_MOV_OPCODES: &[u8] = &[0xB8, 0xB9, 0xBA, 0xBB, 0xBE, 0xBF];
 
-fn find_i386_mov_xrefs(code: &[u8]) -> Vec<u64> {
+fn find_i386_mov_xrefs(code: &[u8], base_addr: u64) -> Vec<(u64, u64)> {
     let mut results = Vec::new();
     if code.len() < 5 {
         return results;
     }
     for i in 0..code.len() - 4 {
         if I386_MOV_OPCODES.contains(&code[i]) {
-            results.push(read_u32_le(code, i + 1) as u64);
+            results.push((base_addr + i as u64, read_u32_le(code, i + 1) as u64));
         }
     }
     results
 }
 
+/// Code references as `(instruction address, target address)` pairs.
 fn collect_xrefs_from_sections(
     sections: &[SectionInfo],
     data: &[u8],
@@ -212,7 +248,7 @@ fn collect_xrefs_from_sections(
     machine: u16,
     image_low: u64,
     image_high: u64,
-) -> Vec<u64> {
+) -> Vec<(u64, u64)> {
     let mut all_xrefs = Vec::new();
 
     for s in sections {
@@ -225,15 +261,15 @@ fn collect_xrefs_from_sections(
         let mut xrefs = match machine {
             IMAGE_FILE_MACHINE_AMD64 => find_amd64_lea_xrefs(code, section_base),
             IMAGE_FILE_MACHINE_I386 => {
-                let mut x = find_i386_lea_xrefs(code);
-                x.extend(find_i386_push_xrefs(code));
-                x.extend(find_i386_mov_xrefs(code));
+                let mut x = find_i386_lea_xrefs(code, section_base);
+                x.extend(find_i386_push_xrefs(code, section_base));
+                x.extend(find_i386_mov_xrefs(code, section_base));
                 x
             }
             _ => Vec::new(),
         };
 
-        xrefs.retain(|&addr| addr >= image_low && addr < image_high);
+        xrefs.retain(|&(_, addr)| addr >= image_low && addr < image_high);
         all_xrefs.extend(xrefs);
     }
 
@@ -243,6 +279,8 @@ fn collect_xrefs_from_sections(
 // --- Struct string candidates ---
 
 struct StructStringCandidate {
+    /// Offset of the `(pointer, length)` pair within the section.
+    pair_offset: usize,
     address: u64,
     length: u64,
 }
@@ -289,12 +327,17 @@ fn get_struct_string_candidates_with_pointer_size(
             continue;
         }
 
-        results.push(StructStringCandidate { address, length });
+        results.push(StructStringCandidate {
+            pair_offset: (i - 1) * word_size,
+            address,
+            length,
+        });
     }
 
     results
 }
 
+/// Strings read through `(pointer, length)` pairs, each with the address of its pair.
 fn collect_struct_strings(
     sections: &[SectionInfo],
     data: &[u8],
@@ -303,7 +346,7 @@ fn collect_struct_strings(
     image_low: u64,
     image_high: u64,
     max_section_size: u64,
-) -> Vec<ExtractedString> {
+) -> Vec<(ExtractedString, u64)> {
     let machine = pe.header.coff_header.machine;
     let psize: usize = match machine {
         IMAGE_FILE_MACHINE_AMD64 => 64,
@@ -355,11 +398,9 @@ fn collect_struct_strings(
                 .wrapping_add(target_section.pointer_to_raw_data);
 
             let len = c.length as usize;
-            if let Some(s) = read_exact_string_at(data, raw_offset, len) {
-                results.push(ExtractedString {
-                    string: s,
-                    offset: raw_offset,
-                });
+            let pair_va = image_base + s.virtual_address as u64 + c.pair_offset as u64;
+            if let Some(string) = read_exact_string_at(data, raw_offset, len) {
+                results.push((ExtractedString::new(string, raw_offset), pair_va));
             }
         }
     }
@@ -425,8 +466,8 @@ pub fn extract_rust_strings(data: &[u8], min_length: usize) -> Vec<ExtractedStri
     let code_xrefs = collect_xrefs_from_sections(&sections, data, image_base, machine, image_low, image_high);
     debug!("code xrefs found: {}", code_xrefs.len());
 
-    let mut code_strings: Vec<ExtractedString> = Vec::new();
-    for &addr in &code_xrefs {
+    let mut code_strings: Vec<(ExtractedString, u64)> = Vec::new();
+    for &(_, addr) in &code_xrefs {
         let raw = addr
             .wrapping_sub(image_base)
             .wrapping_sub(rdata_va as u64)
@@ -435,26 +476,118 @@ pub fn extract_rust_strings(data: &[u8], min_length: usize) -> Vec<ExtractedStri
             continue;
         }
         if let Some(s) = read_string_at(data, raw) {
-            code_strings.push(ExtractedString {
-                string: s,
-                offset: raw,
-            });
+            code_strings.push((ExtractedString::new(s, raw), addr));
         }
     }
     debug!("code xref strings: {}", code_strings.len());
 
-    // 3. Merge, deduplicate by offset, filter by min_length
-    let mut all_strings = struct_strings;
-    all_strings.extend(code_strings);
-    all_strings.sort_by_key(|s| s.offset);
-    all_strings.dedup_by_key(|s| s.offset);
-    all_strings.retain(|s| s.string.len() >= min_length);
+    let mut sites_by_target: HashMap<u64, Vec<u64>> = HashMap::new();
+    for &(site, target) in &code_xrefs {
+        sites_by_target.entry(target).or_default().push(site);
+    }
+    let locations = location_sites(&struct_strings, &sites_by_target);
+
+    // 3. Merge, deduplicate by offset, filter by min_length. Every address
+    // a string is used through is kept for step 4.
+    let mut merged: BTreeMap<usize, (ExtractedString, Vec<u64>)> = BTreeMap::new();
+    for (string, address) in struct_strings.into_iter().chain(code_strings) {
+        merged.entry(string.offset).or_insert_with(|| (string, Vec::new())).1.push(address);
+    }
+    merged.retain(|_, (s, _)| s.string.len() >= min_length);
+
+    // 4. Code context: nearest panic Location and enclosing COFF symbol
+    let symbols = function_symbols(&pe, data, image_base);
+    debug!("panic location sites: {}, function symbols: {}", locations.len(), symbols.len());
+
+    let mut all_strings = Vec::with_capacity(merged.len());
+    for (_, (mut string, addresses)) in merged {
+        let mut sites: Vec<u64> = addresses
+            .iter()
+            .filter_map(|address| sites_by_target.get(address))
+            .flatten()
+            .copied()
+            .collect();
+        sites.sort_unstable();
+        string.source_path = nearest_location(&locations, &sites);
+        string.symbol = enclosing_symbol(&symbols, &sites);
+        all_strings.push(string);
+    }
 
     debug!("final extracted strings: {}", all_strings.len());
 
     all_strings
 }
 
+// --- Code context ---
+
+/// Code addresses that reference a panic `Location`, i.e. a `(pointer, length)`
+/// pair whose string is a `.rs` path, sorted by address.
+fn location_sites(
+    struct_strings: &[(ExtractedString, u64)],
+    sites_by_target: &HashMap<u64, Vec<u64>>,
+) -> Vec<(u64, String)> {
+    let mut sites: Vec<(u64, String)> = struct_strings
+        .iter()
+        .filter(|(s, _)| s.string.ends_with(".rs"))
+        .filter_map(|(s, pair_va)| sites_by_target.get(pair_va).map(|sites| (s, sites)))
+        .flat_map(|(s, sites)| sites.iter().map(|&site| (site, s.string.clone())))
+        .collect();
+    sites.sort();
+    sites
+}
+
+/// std, core and alloc code is inlined everywhere, so their `Location`s only
+/// win when no other one is in range.
+pub(crate) fn is_std_location(path: &str) -> bool {
+    let path = path.replace('\\', "/");
+    path.contains("library/") || path.starts_with("/rustc/") || path.starts_with("/rust/deps/")
+}
+
+fn nearest_location(locations: &[(u64, String)], sites: &[u64]) -> Option<String> {
+    let mut best: Option<(bool, u64, &String)> = None;
+    for &site in sites {
+        let start = locations.partition_point(|(address, _)| *address + MAX_LOCATION_DISTANCE < site);
+        for (address, path) in locations[start..].iter().take_while(|(a, _)| *a <= site + MAX_LOCATION_DISTANCE) {
+            let candidate = (is_std_location(path), address.abs_diff(site), path);
+            if best.is_none_or(|b| (candidate.0, candidate.1) < (b.0, b.1)) {
+                best = Some(candidate);
+            }
+        }
+    }
+    best.map(|(_, _, path)| path.clone())
+}
+
+/// Function symbols from the COFF symbol table, sorted by address. Release
+/// builds with the MSVC toolchain have none; MinGW builds keep them unless stripped.
+fn function_symbols(pe: &PE, data: &[u8], image_base: u64) -> Vec<(u64, String)> {
+    let coff = &pe.header.coff_header;
+    let Ok(Some(symbols)) = coff.symbols(data) else { return Vec::new() };
+    let strtab = coff.strings(data).ok().flatten();
+
+    let mut functions: Vec<(u64, String)> = symbols
+        .iter()
+        .filter_map(|(_, inline_name, symbol)| {
+            if symbol.section_number <= 0 || symbol.derived_type() != IMAGE_SYM_DTYPE_FUNCTION {
+                return None;
+            }
+            let section = pe.sections.get(symbol.section_number as usize - 1)?;
+            let name = match inline_name {
+                Some(name) => name,
+                None => symbol.name(strtab.as_ref()?).ok()?,
+            };
+            Some((image_base + section.virtual_address as u64 + symbol.value as u64, name.to_string()))
+        })
+        .collect();
+    functions.sort();
+    functions
+}
+
+fn enclosing_symbol(symbols: &[(u64, String)], sites: &[u64]) -> Option<String> {
+    let site = *sites.first()?;
+    let idx = symbols.partition_point(|(address, _)| *address <= site);
+    symbols.get(idx.checked_sub(1)?).map(|(_, name)| name.clone())
+}
+
 pub fn is_pe(data: &[u8]) -> bool {
     data.len() > 2 && data[0] == b'M' && data[1] == b'Z'
 }
@@ -0,0 +1,154 @@
+use goblin::elf::Elf;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use log::debug;
+
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+/// A toolchain record embedded by the compiler or linker.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Producer {
+    /// `elf_comment`, `dwarf_producer` or `wasm_producers`.
+    pub source: String,
+    /// The record as found, e.g. `rustc version 1.75.0 (82e1608df 2023-12-21)`
+    /// or `processed-by: rustc 1.75.0 (82e1608df 2023-12-21)`.
+    pub value: String,
+}
+
+impl Producer {
+    fn new(source: &str, value: &str) -> Self {
+        Producer { source: source.to_string(), value: value.to_string() }
+    }
+}
+
+/// Rust version and abbreviated commit hash named by a producer record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProducerRustc {
+    pub version: String,
+    pub hash: Option<String>,
+}
+
+/// NUL-separated entries of the ELF `.comment` section.
+fn elf_comments(content: &[u8]) -> Vec<Producer> {
+    let Ok(elf) = Elf::parse(content) else { return Vec::new() };
+    let mut producers = Vec::new();
+    for section in &elf.section_headers {
+        if elf.shdr_strtab.get_at(section.sh_name) != Some(".comment") {
+            continue;
+        }
+        let Some(range) = section.file_range() else { continue };
+        let Some(data) = content.get(range) else { continue };
+        for entry in data.split(|b| *b == 0) {
+            let entry = String::from_utf8_lossy(entry);
+            let entry = entry.trim();
+            if !entry.is_empty() {
+                producers.push(Producer::new("elf_comment", entry));
+            }
+        }
+    }
+    producers
+}
+
+/// `DW_AT_producer` values rustc writes, wherever the debug info lives.
+fn dwarf_producers(content: &[u8]) -> Result<Vec<Producer>, Box<dyn std::error::Error>> {
+    let re = regex::bytes::Regex::new(r"clang LLVM \(rustc version [^\x00()]*(?:\([^\x00()]*\))?\)")?;
+    let mut producers: Vec<Producer> = Vec::new();
+    for mat in re.find_iter(content) {
+        let value = String::from_utf8_lossy(mat.as_bytes());
+        if !producers.iter().any(|p| p.value == value) {
+            producers.push(Producer::new("dwarf_producer", &value));
+        }
+    }
+    Ok(producers)
+}
+
+fn read_leb128(data: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut result: usize = 0;
+    for shift in (0..35).step_by(7) {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        result |= usize::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+    }
+    None
+}
+
+fn read_name<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a str> {
+    let len = read_leb128(data, pos)?;
+    let name = std::str::from_utf8(data.get(*pos..pos.checked_add(len)?)?).ok()?;
+    *pos += len;
+    Some(name)
+}
+
+/// Fields of the `producers` custom section: `language`, `processed-by` and
+/// `sdk`, each a list of name and version pairs.
+fn parse_producers_section(data: &[u8]) -> Option<Vec<Producer>> {
+    let mut pos = 0;
+    let mut producers = Vec::new();
+    for _ in 0..read_leb128(data, &mut pos)? {
+        let field = read_name(data, &mut pos)?;
+        for _ in 0..read_leb128(data, &mut pos)? {
+            let name = read_name(data, &mut pos)?;
+            let version = read_name(data, &mut pos)?;
+            let value = format!("{}: {} {}", field, name, version);
+            producers.push(Producer::new("wasm_producers", value.trim_end()));
+        }
+    }
+    Some(producers)
+}
+
+fn wasm_producers(content: &[u8]) -> Vec<Producer> {
+    if !content.starts_with(WASM_MAGIC) || content.len() < 8 {
+        return Vec::new();
+    }
+    let mut pos = 8;
+    while pos < content.len() {
+        let id = content[pos];
+        pos += 1;
+        let Some(size) = read_leb128(content, &mut pos) else { break };
+        let Some(section) = pos.checked_add(size).and_then(|end| content.get(pos..end)) else { break };
+        pos += size;
+        if id != 0 {
+            continue;
+        }
+        let mut name_pos = 0;
+        if read_name(section, &mut name_pos) == Some("producers") {
+            return parse_producers_section(&section[name_pos..]).unwrap_or_default();
+        }
+    }
+    Vec::new()
+}
+
+/// Collect ELF `.comment` entries, DWARF producers and the WebAssembly
+/// `producers` section.
+pub fn extract_producers(content: &[u8]) -> Result<Vec<Producer>, Box<dyn std::error::Error>> {
+    let mut producers = elf_comments(content);
+    producers.extend(dwarf_producers(content)?);
+    producers.extend(wasm_producers(content));
+    debug!("Found {} producer records", producers.len());
+    Ok(producers)
+}
+
+/// The Rust version, with its abbreviated hash, from the first record naming rustc.
+pub fn rustc_version(producers: &[Producer]) -> Result<Option<ProducerRustc>, Box<dyn std::error::Error>> {
+    let re = Regex::new(
+        r"rustc (?:version )?(\d+\.\d+\.\d+(?:-(?:beta(?:\.\d+)?|nightly|dev))?)(?: \(([a-f0-9]{7,40}) \d{4}-\d{2}-\d{2}\))?",
+    )?;
+    Ok(producers.iter().find_map(|p| {
+        let caps = re.captures(&p.value)?;
+        Some(ProducerRustc {
+            version: caps.get(1)?.as_str().to_string(),
+            hash: caps.get(2).map(|h| h.as_str().to_string()),
+        })
+    }))
+}
+
+/// The LLVM version named by an LLD, LLVM or clang producer record.
+pub fn llvm_version(producers: &[Producer]) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let re = Regex::new(r"(?:LLD|LLVM|clang)(?: version)? (\d+\.\d+(?:\.\d+)?)")?;
+    Ok(producers
+        .iter()
+        .find_map(|p| re.captures(&p.value).and_then(|caps| caps.get(1)).map(|v| v.as_str().to_string())))
+}
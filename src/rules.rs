@@ -0,0 +1,247 @@
+use std::fs;
+use std::path::Path;
+use regex::Regex;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use log::{debug, warn};
+use crate::AnalysisResult;
+
+pub const RULES_FILE: &str = "rules.toml";
+
+#[derive(Deserialize, Debug)]
+struct RuleFile {
+    #[serde(default)]
+    rule: Vec<RuleDefinition>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RuleDefinition {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    packages: Vec<PackageDefinition>,
+    #[serde(default)]
+    user_source_paths: Vec<String>,
+    #[serde(default)]
+    rustc_hash: Option<String>,
+    #[serde(default)]
+    rust_version: Option<String>,
+    #[serde(default)]
+    language_strings: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct PackageDefinition {
+    name: String,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct PackageCondition {
+    name: String,
+    version: Option<VersionReq>,
+}
+
+/// A parsed rule. Every condition it sets must hold for it to match; list
+/// conditions need each entry to match at least once.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    packages: Vec<PackageCondition>,
+    user_source_paths: Vec<(String, Regex)>,
+    rustc_hash: Option<String>,
+    rust_version: Option<VersionReq>,
+    language_strings: Vec<Regex>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RuleMatch {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    /// What satisfied each condition, e.g. `package:clap@3.2.23` or `string:...`.
+    pub matched: Vec<String>,
+}
+
+fn normalize_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace('_', "-")
+}
+
+/// Translate a path glob into an anchored regex. `**` crosses directories,
+/// `*` and `?` do not; `\` and `/` are interchangeable.
+fn glob_to_regex(glob: &str) -> Result<Regex, regex::Error> {
+    let glob = glob.replace('\\', "/");
+    let mut pattern = String::from("(?i)^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches no directory at all.
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern)
+}
+
+fn compile(definition: RuleDefinition) -> Result<Rule, Box<dyn std::error::Error>> {
+    let name = definition.name;
+    let packages = definition
+        .packages
+        .into_iter()
+        .map(|p| {
+            let version = p
+                .version
+                .map(|v| VersionReq::parse(&v).map_err(|e| format!("rule {}: invalid version range {:?}: {}", name, v, e)))
+                .transpose()?;
+            Ok(PackageCondition { name: normalize_name(&p.name), version })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let user_source_paths = definition
+        .user_source_paths
+        .into_iter()
+        .map(|g| {
+            let re = glob_to_regex(&g).map_err(|e| format!("rule {}: invalid glob {:?}: {}", name, g, e))?;
+            Ok((g, re))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let rust_version = definition
+        .rust_version
+        .map(|v| VersionReq::parse(&v).map_err(|e| format!("rule {}: invalid rust_version {:?}: {}", name, v, e)))
+        .transpose()?;
+    let language_strings = definition
+        .language_strings
+        .iter()
+        .map(|r| Regex::new(r).map_err(|e| format!("rule {}: invalid regex {:?}: {}", name, r, e)))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let rule = Rule {
+        description: definition.description,
+        tags: definition.tags,
+        packages,
+        user_source_paths,
+        rustc_hash: definition.rustc_hash.map(|h| h.to_ascii_lowercase()),
+        rust_version,
+        language_strings,
+        name,
+    };
+    if rule.packages.is_empty()
+        && rule.user_source_paths.is_empty()
+        && rule.rustc_hash.is_none()
+        && rule.rust_version.is_none()
+        && rule.language_strings.is_empty()
+    {
+        return Err(format!("rule {} has no conditions", rule.name).into());
+    }
+    Ok(rule)
+}
+
+pub fn parse_rules(content: &str) -> Result<Vec<Rule>, Box<dyn std::error::Error>> {
+    let file: RuleFile = toml::from_str(content)?;
+    file.rule.into_iter().map(compile).collect()
+}
+
+pub fn load_rules(path: &Path) -> Result<Vec<Rule>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)?;
+    parse_rules(&content).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+/// Rules from `rules.toml` in the current directory, if present.
+pub fn load_default_rules() -> Option<Vec<Rule>> {
+    if !Path::new(RULES_FILE).exists() {
+        debug!("No rules loaded: {} not found", RULES_FILE);
+        return None;
+    }
+    match load_rules(Path::new(RULES_FILE)) {
+        Ok(rules) => Some(rules),
+        Err(e) => {
+            warn!("Failed to load rules: {}", e);
+            None
+        }
+    }
+}
+
+fn match_package(condition: &PackageCondition, result: &AnalysisResult) -> Option<String> {
+    result.packages.iter().find_map(|package| {
+        let (name, version) = package.crate_name_and_version();
+        if normalize_name(&name) != condition.name {
+            return None;
+        }
+        match (&condition.version, &version) {
+            (None, None) => Some(format!("package:{}", name)),
+            (None, Some(version)) => Some(format!("package:{}@{}", name, version)),
+            (Some(req), Some(version)) => {
+                let parsed = Version::parse(version).ok()?;
+                req.matches(&parsed).then(|| format!("package:{}@{}", name, version))
+            }
+            // A range cannot be checked against an unversioned registry path.
+            (Some(_), None) => None,
+        }
+    })
+}
+
+fn evaluate(rule: &Rule, result: &AnalysisResult) -> Option<Vec<String>> {
+    let mut matched = Vec::new();
+    for condition in &rule.packages {
+        matched.push(match_package(condition, result)?);
+    }
+    for (glob, re) in &rule.user_source_paths {
+        let mut paths: Vec<&String> = result.user_source_paths.iter().collect();
+        paths.sort();
+        let path = paths.into_iter().find(|p| re.is_match(&p.replace('\\', "/")))?;
+        debug!("rule {}: {} matched {}", rule.name, glob, path);
+        matched.push(format!("user_source_path:{}", path));
+    }
+    if let Some(prefix) = &rule.rustc_hash {
+        let hash = result.rustc_hash.as_ref().filter(|h| h.starts_with(prefix.as_str()))?;
+        matched.push(format!("rustc_hash:{}", hash));
+    }
+    if let Some(req) = &rule.rust_version {
+        let version = result.rust_version.as_ref()?;
+        // Nightly and beta toolchains are matched as the release they became.
+        let release = crate::versions::release_version(version)?;
+        if !req.matches(&Version::parse(&release).ok()?) {
+            return None;
+        }
+        matched.push(format!("rust_version:{}", version));
+    }
+    for re in &rule.language_strings {
+        let s = result.language_strings.iter().find(|s| re.is_match(&s.string))?;
+        matched.push(format!("string:{}", s.string));
+    }
+    Some(matched)
+}
+
+/// Names, tags and matched evidence of every rule the result satisfies.
+pub fn evaluate_rules(rules: &[Rule], result: &AnalysisResult) -> Vec<RuleMatch> {
+    rules
+        .iter()
+        .filter_map(|rule| {
+            evaluate(rule, result).map(|matched| RuleMatch {
+                name: rule.name.clone(),
+                description: rule.description.clone(),
+                tags: rule.tags.clone(),
+                matched,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::{debug, info};
+use crate::versions::{format_date, parse_date};
+use crate::{target, AnalysisResult, STD_DEPENDENCY_CRATES};
+
+/// Package names cannot start with a digit, which hash-named samples do.
+fn package_name(result: &AnalysisResult) -> String {
+    let stem = Path::new(&result.file.name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let sanitized: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '-' })
+        .collect();
+    match sanitized.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => sanitized,
+        _ => "rebuild".to_string(),
+    }
+}
+
+fn cargo_toml(result: &AnalysisResult) -> String {
+    let mut versions: BTreeMap<String, Vec<Option<String>>> = BTreeMap::new();
+    for package in &result.packages {
+        let (name, version) = package.crate_name_and_version();
+        let entry = versions.entry(name).or_default();
+        if !entry.contains(&version) {
+            entry.push(version);
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str(&format!(
+        "# Generated by rustre from {} ({}).\n\
+         # Dependencies were inferred from embedded registry paths and may be incomplete.\n\n",
+        result.file.name, result.file.sha256
+    ));
+    out.push_str("[package]\n");
+    out.push_str(&format!("name = \"{}\"\n", package_name(result)));
+    out.push_str("version = \"0.1.0\"\n");
+    out.push_str("edition = \"2021\"\n");
+    out.push_str("publish = false\n\n");
+    out.push_str("[dependencies]\n");
+
+    for (name, mut crate_versions) in versions {
+        crate_versions.sort();
+        let is_std_dependency = STD_DEPENDENCY_CRATES.contains(&name.as_str());
+        for (i, version) in crate_versions.iter().enumerate() {
+            // Cargo needs a distinct key for each extra version of a crate.
+            let key = match (i, version) {
+                (0, _) => name.clone(),
+                (_, Some(v)) => format!("{}-{}", name, v.replace(['.', '+'], "_")),
+                (_, None) => format!("{}-unversioned", name),
+            };
+            let requirement = match version {
+                Some(v) => format!("\"={}\"", v),
+                None => "\"*\"".to_string(),
+            };
+            let line = if key == name {
+                format!("{} = {}", key, requirement)
+            } else {
+                format!("{} = {{ package = \"{}\", version = {} }}", key, name, requirement)
+            };
+            if is_std_dependency {
+                out.push_str(&format!("# {}  # usually linked by std, not declared by the program\n", line));
+            } else if version.is_none() {
+                out.push_str(&format!("{}  # version not recoverable from the binary\n", line));
+            } else {
+                out.push_str(&format!("{}\n", line));
+            }
+        }
+    }
+    out
+}
+
+/// rustup channel for a resolved version. Nightly and beta builds go by
+/// channel and build date, one day after the commit date rustc reports.
+fn toolchain_channel(result: &AnalysisResult, version: &str) -> String {
+    if version.starts_with("nightly-") || version.starts_with("beta-") || !version.contains('-') {
+        return version.to_string();
+    }
+    let channel = if version.contains("nightly") { "nightly" } else { "beta" };
+    let commit_date = result
+        .toolchain
+        .as_ref()
+        .filter(|t| t.version == version)
+        .and_then(|t| t.date.as_deref())
+        .and_then(|date| parse_date(date).ok());
+    match commit_date {
+        Some(days) => format!("{}-{}", channel, format_date(days + 1)),
+        None => channel.to_string(),
+    }
+}
+
+fn rust_toolchain_toml(result: &AnalysisResult, target: Option<&str>) -> String {
+    let mut out = String::from("[toolchain]\n");
+    match &result.rust_version {
+        Some(version) => {
+            let channel = toolchain_channel(result, version);
+            if channel != *version {
+                out.push_str(&format!("# built with rustc {}\n", version));
+            }
+            out.push_str(&format!("channel = \"{}\"\n", channel));
+        }
+        None => out.push_str("# rustc version could not be resolved from the binary\nchannel = \"stable\"\n"),
+    }
+    if let Some(target) = target {
+        out.push_str(&format!("targets = [\"{}\"]\n", target));
+    }
+    out
+}
+
+fn cargo_config_toml(target: &str) -> String {
+    format!("[build]\ntarget = \"{}\"\n", target)
+}
+
+/// Write a Cargo project reproducing the binary's dependency set and
+/// toolchain into `out_dir`. Returns the files written.
+pub fn write_scaffold(
+    result: &AnalysisResult,
+    target: Option<&str>,
+    out_dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut files = vec![
+        (out_dir.join("Cargo.toml"), cargo_toml(result)),
+        (out_dir.join("rust-toolchain.toml"), rust_toolchain_toml(result, target)),
+        (out_dir.join("src").join("main.rs"), "fn main() {}\n".to_string()),
+    ];
+    if let Some(target) = target {
+        files.push((out_dir.join(".cargo").join("config.toml"), cargo_config_toml(target)));
+    }
+
+    if let Some((path, _)) = files.iter().find(|(path, _)| path.exists()) {
+        return Err(format!("{} already exists, refusing to overwrite", path.display()).into());
+    }
+
+    let mut written = Vec::new();
+    for (path, content) in files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, content)?;
+        debug!("Wrote {}", path.display());
+        written.push(path);
+    }
+    info!("Scaffold written to {}", out_dir.display());
+    Ok(written)
+}
+
+pub fn scaffold_binary(
+    file_path: &str,
+    result: &AnalysisResult,
+    out_dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    // Reports saved before target inference carry no triple.
+    let target = match &result.target.triple {
+        Some(triple) => Some(triple.clone()),
+        None => target::infer_target(&fs::read(file_path)?, &result.framework_source_paths)?.triple,
+    };
+    debug!("Target triple: {:?}", target);
+    write_scaffold(result, target.as_deref(), out_dir)
+}
@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use crate::versions::civil_from_days;
+use crate::{to_hex, AnalysisResult, Package};
+
+const SPDX_VERSION: &str = "SPDX-2.3";
+const DOCUMENT_ID: &str = "SPDXRef-DOCUMENT";
+const BINARY_ID: &str = "SPDXRef-Binary";
+const INFERENCE_NOTE: &str = "Crate list inferred heuristically by rustre from Cargo registry paths \
+embedded in the binary; it was not derived from a Cargo.lock and may be incomplete.";
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Document {
+    pub spdx_version: &'static str,
+    pub data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    pub spdx_id: &'static str,
+    pub name: String,
+    pub document_namespace: String,
+    pub creation_info: CreationInfo,
+    pub packages: Vec<SpdxPackage>,
+    pub relationships: Vec<Relationship>,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CreationInfo {
+    pub created: String,
+    pub creators: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxPackage {
+    #[serde(rename = "SPDXID")]
+    pub spdx_id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_info: Option<String>,
+    pub download_location: String,
+    pub files_analyzed: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub checksums: Vec<Checksum>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub external_refs: Vec<ExternalRef>,
+    pub primary_package_purpose: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_info: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Checksum {
+    pub algorithm: &'static str,
+    pub checksum_value: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalRef {
+    pub reference_category: &'static str,
+    pub reference_type: &'static str,
+    pub reference_locator: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Relationship {
+    pub spdx_element_id: String,
+    pub relationship_type: &'static str,
+    pub related_spdx_element: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Annotation {
+    pub annotation_date: String,
+    pub annotation_type: &'static str,
+    pub annotator: String,
+    pub comment: String,
+}
+
+/// Format a UNIX timestamp as an SPDX `YYYY-MM-DDThh:mm:ssZ` UTC date.
+fn format_utc(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, rem / 3_600, (rem % 3_600) / 60, rem % 60
+    )
+}
+
+/// UUID for the document namespace, which SPDX requires to be unique per
+/// document: the file hash mixed with the creation time and process id.
+fn document_uuid(result: &AnalysisResult) -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    let seed = format!("{}\0{}\0{}", result.file.sha256, nanos, std::process::id());
+    let hash = to_hex(&Sha256::digest(seed.as_bytes()));
+    format!("{}-{}-4{}-8{}-{}", &hash[0..8], &hash[8..12], &hash[13..16], &hash[17..20], &hash[20..32])
+}
+
+/// SPDXID of the analyzed binary, derived from its hash when the report has one.
+fn binary_id(result: &AnalysisResult) -> String {
+    match result.file.sha256.get(..16) {
+        Some(prefix) => spdx_ref("Binary", prefix),
+        None => BINARY_ID.to_string(),
+    }
+}
+
+fn now_utc() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format_utc(secs)
+}
+
+/// SPDX identifiers may only contain letters, digits, `.` and `-`.
+fn spdx_ref(kind: &str, value: &str) -> String {
+    let sanitized: String = value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '-' })
+        .collect();
+    format!("SPDXRef-{}-{}", kind, sanitized)
+}
+
+fn tool_name() -> String {
+    format!("Tool: {}-{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+fn crate_package(package: &Package) -> SpdxPackage {
+    let (name, version) = package.crate_name_and_version();
+    let id = match &version {
+        Some(version) => format!("{}-{}", name, version),
+        None => name.clone(),
+    };
+    SpdxPackage {
+        spdx_id: spdx_ref("Crate", &id),
+        name,
+        version_info: version,
+        download_location: "NOASSERTION".to_string(),
+        files_analyzed: false,
+        checksums: Vec::new(),
+        external_refs: vec![ExternalRef {
+            reference_category: "PACKAGE-MANAGER",
+            reference_type: "purl",
+            reference_locator: package.purl(),
+        }],
+        primary_package_purpose: "LIBRARY",
+        source_info: Some(format!("inferred from embedded path {}", package.path)),
+        comment: None,
+    }
+}
+
+fn binary_package(result: &AnalysisResult, spdx_id: String) -> SpdxPackage {
+    let comment = match (&result.toolchain, &result.rust_version, &result.rustc_hash) {
+        (Some(toolchain), _, Some(hash)) => Some(format!("Built with {} ({})", toolchain.describe(), hash)),
+        (_, Some(version), Some(hash)) => Some(format!("Built with rustc {} ({})", version, hash)),
+        (_, None, Some(hash)) => Some(format!("Built with rustc commit {}", hash)),
+        (_, Some(version), None) => Some(format!("Built with rustc {}", version)),
+        (_, None, None) => None,
+    };
+    SpdxPackage {
+        spdx_id,
+        name: result.file.name.clone(),
+        version_info: None,
+        download_location: "NOASSERTION".to_string(),
+        files_analyzed: false,
+        checksums: vec![
+            Checksum { algorithm: "SHA1", checksum_value: result.file.sha1.clone() },
+            Checksum { algorithm: "SHA256", checksum_value: result.file.sha256.clone() },
+        ],
+        external_refs: Vec::new(),
+        primary_package_purpose: "APPLICATION",
+        source_info: None,
+        comment,
+    }
+}
+
+pub fn to_spdx(result: &AnalysisResult) -> Document {
+    let created = now_utc();
+
+    // Collapse crates found under several registry roots into one package.
+    let unique: BTreeMap<String, &Package> = result
+        .packages
+        .iter()
+        .map(|p| (p.purl(), p))
+        .collect();
+
+    let binary_id = binary_id(result);
+    let mut packages = vec![binary_package(result, binary_id.clone())];
+    let mut relationships = vec![Relationship {
+        spdx_element_id: DOCUMENT_ID.to_string(),
+        relationship_type: "DESCRIBES",
+        related_spdx_element: binary_id.clone(),
+    }];
+    for package in unique.values() {
+        let spdx_package = crate_package(package);
+        relationships.push(Relationship {
+            spdx_element_id: binary_id.clone(),
+            relationship_type: "CONTAINS",
+            related_spdx_element: spdx_package.spdx_id.clone(),
+        });
+        packages.push(spdx_package);
+    }
+
+    Document {
+        spdx_version: SPDX_VERSION,
+        data_license: "CC0-1.0",
+        spdx_id: DOCUMENT_ID,
+        name: result.file.name.clone(),
+        document_namespace: format!(
+            "https://spdx.org/spdxdocs/{}-{}",
+            env!("CARGO_PKG_NAME"),
+            document_uuid(result)
+        ),
+        creation_info: CreationInfo {
+            created: created.clone(),
+            creators: vec![tool_name()],
+        },
+        packages,
+        relationships,
+        annotations: vec![Annotation {
+            annotation_date: created,
+            annotation_type: "OTHER",
+            annotator: tool_name(),
+            comment: INFERENCE_NOTE.to_string(),
+        }],
+    }
+}
+
+/// Render an SPDX document in the tag-value format.
+pub fn to_tag_value(doc: &Document) -> Result<String, std::fmt::Error> {
+    let mut out = String::new();
+    writeln!(out, "SPDXVersion: {}", doc.spdx_version)?;
+    writeln!(out, "DataLicense: {}", doc.data_license)?;
+    writeln!(out, "SPDXID: {}", doc.spdx_id)?;
+    writeln!(out, "DocumentName: {}", doc.name)?;
+    writeln!(out, "DocumentNamespace: {}", doc.document_namespace)?;
+    for creator in &doc.creation_info.creators {
+        writeln!(out, "Creator: {}", creator)?;
+    }
+    writeln!(out, "Created: {}", doc.creation_info.created)?;
+
+    for annotation in &doc.annotations {
+        writeln!(out)?;
+        writeln!(out, "Annotator: {}", annotation.annotator)?;
+        writeln!(out, "AnnotationDate: {}", annotation.annotation_date)?;
+        writeln!(out, "AnnotationType: {}", annotation.annotation_type)?;
+        writeln!(out, "SPDXREF: {}", doc.spdx_id)?;
+        writeln!(out, "AnnotationComment: <text>{}</text>", annotation.comment)?;
+    }
+
+    for package in &doc.packages {
+        writeln!(out)?;
+        writeln!(out, "PackageName: {}", package.name)?;
+        writeln!(out, "SPDXID: {}", package.spdx_id)?;
+        if let Some(version) = &package.version_info {
+            writeln!(out, "PackageVersion: {}", version)?;
+        }
+        writeln!(out, "PackageDownloadLocation: {}", package.download_location)?;
+        writeln!(out, "FilesAnalyzed: {}", package.files_analyzed)?;
+        for checksum in &package.checksums {
+            writeln!(out, "PackageChecksum: {}: {}", checksum.algorithm, checksum.checksum_value)?;
+        }
+        writeln!(out, "PrimaryPackagePurpose: {}", package.primary_package_purpose)?;
+        if let Some(source_info) = &package.source_info {
+            writeln!(out, "PackageSourceInfo: <text>{}</text>", source_info)?;
+        }
+        if let Some(comment) = &package.comment {
+            writeln!(out, "PackageComment: <text>{}</text>", comment)?;
+        }
+        for external_ref in &package.external_refs {
+            writeln!(
+                out,
+                "ExternalRef: {} {} {}",
+                external_ref.reference_category, external_ref.reference_type, external_ref.reference_locator
+            )?;
+        }
+    }
+
+    writeln!(out)?;
+    for relationship in &doc.relationships {
+        writeln!(
+            out,
+            "Relationship: {} {} {}",
+            relationship.spdx_element_id, relationship.relationship_type, relationship.related_spdx_element
+        )?;
+    }
+    Ok(out)
+}
@@ -0,0 +1,139 @@
+use std::collections::{BTreeMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+/// Standard library crates and the source roots they appear under.
+const STD_CRATES: &[(&str, &[&str])] = &[
+    ("std", &["std/src/", "libstd/"]),
+    ("core", &["core/src/", "libcore/"]),
+    ("alloc", &["alloc/src/", "liballoc/"]),
+    ("proc_macro", &["proc_macro/src/", "libproc_macro/"]),
+];
+
+/// Path components that tie a module to one target family.
+const PLATFORM_COMPONENTS: &[(&str, &str)] = &[
+    ("windows", "windows"),
+    ("unix", "unix"),
+    ("linux", "linux"),
+    ("android", "android"),
+    ("macos", "macos"),
+    ("apple", "macos"),
+    ("darwin", "macos"),
+    ("ios", "ios"),
+    ("freebsd", "freebsd"),
+    ("wasi", "wasi"),
+    ("wasm", "wasm"),
+    ("hermit", "hermit"),
+    ("sgx", "sgx"),
+    ("uefi", "uefi"),
+];
+
+/// Modules whose second level is worth keeping apart, e.g. `os::windows`
+/// versus `os::unix`, or `sync::mpsc` versus `sync::once`.
+const NESTED_MODULES: &[&str] = &["collections", "os", "sync", "sys"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StdModule {
+    #[serde(rename = "crate")]
+    pub krate: String,
+    /// Module path such as `net`, `os::windows` or `sync::mpsc`.
+    pub module: String,
+    /// Full module paths of the compiled-in source files.
+    pub files: Vec<String>,
+    /// Target family the module is specific to, if any.
+    pub platform: Option<String>,
+}
+
+/// Strip everything before the std/core/alloc crate root so paths compare
+/// the same whether they came from `/rustc/<hash>/library/` or a remapped root.
+pub(crate) fn std_relative_path(path: &str) -> String {
+    let normalized = path.replace('\\', "/");
+    if let Some(idx) = normalized.find("library/") {
+        return normalized[idx + "library/".len()..].to_string();
+    }
+    // Pre-1.47 layout: src/libstd, src/libcore, ...
+    if let Some(idx) = normalized.find("src/lib") {
+        return normalized[idx + "src/".len()..].to_string();
+    }
+    normalized.trim_start_matches('/').to_string()
+}
+
+/// Split a std-relative path into its crate and module components. The flag
+/// is set for `mod.rs`/`lib.rs` files, whose last component is a directory.
+fn parse_std_path(relative: &str) -> Option<(&'static str, Vec<String>, bool)> {
+    if relative.contains("..") {
+        return None;
+    }
+    let (krate, rest) = STD_CRATES.iter().find_map(|(krate, roots)| {
+        roots
+            .iter()
+            .find_map(|root| relative.strip_prefix(root))
+            .map(|rest| (*krate, rest))
+    })?;
+    let mut components: Vec<String> = rest
+        .trim_end_matches(".rs")
+        .split('/')
+        .filter(|c| !c.is_empty())
+        .map(|c| c.to_string())
+        .collect();
+    let is_directory = matches!(components.last().map(String::as_str), Some("mod") | Some("lib"));
+    if is_directory {
+        components.pop();
+    }
+    Some((krate, components, is_directory))
+}
+
+fn module_key(components: &[String], is_directory: bool) -> Vec<String> {
+    if components.is_empty() {
+        return Vec::new();
+    }
+    let mut depth = 1;
+    if NESTED_MODULES.contains(&components[0].as_str()) {
+        depth = 2;
+        // sys::pal::<platform> since 1.77
+        if components[0] == "sys" && components.get(1).map(String::as_str) == Some("pal") {
+            depth = 3;
+        }
+    }
+    // Keep file-level modules like `os/fd.rs` under `os`, not as a group of their own.
+    let directories = if is_directory { components.len() } else { components.len() - 1 };
+    depth = depth.min(directories).max(1);
+    components[..depth].to_vec()
+}
+
+fn platform_of(components: &[String]) -> Option<String> {
+    components.iter().find_map(|c| {
+        PLATFORM_COMPONENTS
+            .iter()
+            .find(|(component, _)| c == component)
+            .map(|(_, platform)| platform.to_string())
+    })
+}
+
+pub fn map_std_modules(framework_paths: &HashSet<String>) -> Vec<StdModule> {
+    let mut modules: BTreeMap<(&str, String), StdModule> = BTreeMap::new();
+    for path in framework_paths {
+        let relative = std_relative_path(path);
+        let Some((krate, components, is_directory)) = parse_std_path(&relative) else { continue };
+        let key = module_key(&components, is_directory);
+        let module = if key.is_empty() { "(root)".to_string() } else { key.join("::") };
+        modules
+            .entry((krate, module.clone()))
+            .or_insert_with(|| StdModule {
+                krate: krate.to_string(),
+                module,
+                files: Vec::new(),
+                platform: platform_of(&key),
+            })
+            .files
+            .push(components.join("::"));
+    }
+
+    modules
+        .into_values()
+        .map(|mut module| {
+            module.files.sort();
+            module.files.dedup();
+            module
+        })
+        .collect()
+}
@@ -0,0 +1,256 @@
+use std::collections::HashSet;
+use goblin::Object;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use log::debug;
+use crate::find_needles;
+
+const PE_MACHINE_I386: u16 = 0x14c;
+const PE_MACHINE_AMD64: u16 = 0x8664;
+const PE_MACHINE_ARM64: u16 = 0xaa64;
+const ELFOSABI_NETBSD: u8 = 2;
+const ELFOSABI_FREEBSD: u8 = 9;
+const ELFOSABI_OPENBSD: u8 = 12;
+
+/// Byte patterns tying a binary to one C runtime, as `(env, pattern, what it is)`.
+const ENV_MARKERS: &[(&str, &str, &str)] = &[
+    ("musl", r"/lib/ld-musl-[a-z0-9_]+\.so\.1", "musl dynamic loader"),
+    ("musl", r"musl-\d+\.\d+\.\d+", "musl source path"),
+    ("gnu", r"/lib(?:64)?/ld-linux[a-z0-9_\-]*\.so\.\d", "glibc dynamic loader"),
+    ("gnu", r"GLIBC_2\.\d+", "glibc symbol version"),
+    ("gnu", r"libgcc_s(?:_seh-1\.dll|_dw2-1\.dll|\.so\.1)", "libgcc runtime"),
+    ("gnu", r"Mingw-w64 runtime failure", "MinGW runtime message"),
+    ("gnu", r"GCC: \([^)\x00]*MinGW[^)\x00]*\)", "MinGW GCC identification"),
+    ("msvc", r"(?i)vcruntime1\d\d\.dll", "MSVC runtime import"),
+];
+
+/// std `os::` modules that only exist on one operating system.
+const OS_MODULES: &[(&str, &str)] = &[
+    ("std/src/os/android/", "android"),
+    ("std/src/os/linux/", "linux"),
+    ("std/src/os/freebsd/", "freebsd"),
+    ("std/src/os/netbsd/", "netbsd"),
+    ("std/src/os/openbsd/", "openbsd"),
+    ("std/src/os/macos/", "macos"),
+    ("std/src/os/ios/", "ios"),
+    ("std/src/os/wasi/", "wasi"),
+    ("std/src/os/windows/", "windows"),
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TargetEvidence {
+    /// `arch`, `os` or `env`.
+    pub field: String,
+    pub value: String,
+    /// What the value was read from, e.g. `PE machine 0x8664` or `std path std/src/os/linux/`.
+    pub source: String,
+}
+
+/// Best guess at the target the binary was compiled for.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TargetInfo {
+    /// Rust target triple such as `x86_64-pc-windows-gnu`.
+    pub triple: Option<String>,
+    pub arch: Option<String>,
+    pub bits: Option<u8>,
+    /// `linux`, `android`, `freebsd`, `netbsd`, `openbsd`, `windows`, `macos`, `ios` or `wasi`.
+    pub os: Option<String>,
+    /// C runtime and ABI: `gnu`, `musl` or `msvc`.
+    pub env: Option<String>,
+    pub evidence: Vec<TargetEvidence>,
+}
+
+impl TargetInfo {
+    fn record(&mut self, field: &str, value: &str, source: String) {
+        self.evidence.push(TargetEvidence { field: field.to_string(), value: value.to_string(), source });
+    }
+}
+
+/// Architecture, bitness, OS and imported libraries from the container header.
+fn read_header(content: &[u8], target: &mut TargetInfo) -> Vec<String> {
+    let mut libraries = Vec::new();
+    let (arch, bits, os, source) = match Object::parse(content) {
+        Ok(Object::Elf(elf)) => {
+            let arch = match elf.header.e_machine {
+                goblin::elf::header::EM_X86_64 => Some("x86_64"),
+                goblin::elf::header::EM_386 => Some("i686"),
+                goblin::elf::header::EM_AARCH64 => Some("aarch64"),
+                goblin::elf::header::EM_ARM => Some("armv7"),
+                goblin::elf::header::EM_RISCV if elf.is_64 => Some("riscv64gc"),
+                goblin::elf::header::EM_MIPS => Some(match (elf.is_64, elf.little_endian) {
+                    (false, false) => "mips",
+                    (false, true) => "mipsel",
+                    (true, false) => "mips64",
+                    (true, true) => "mips64el",
+                }),
+                _ => None,
+            };
+            // Most toolchains leave the OS ABI at System V; std modules refine it later.
+            let os = match elf.header.e_ident[goblin::elf::header::EI_OSABI] {
+                ELFOSABI_NETBSD => "netbsd",
+                ELFOSABI_FREEBSD => "freebsd",
+                ELFOSABI_OPENBSD => "openbsd",
+                _ => "linux",
+            };
+            if let Some(interpreter) = elf.interpreter {
+                libraries.push(interpreter.to_string());
+            }
+            libraries.extend(elf.libraries.iter().map(|l| l.to_string()));
+            (arch, if elf.is_64 { 64 } else { 32 }, os, format!("ELF machine {}", elf.header.e_machine))
+        }
+        Ok(Object::PE(pe)) => {
+            let machine = pe.header.coff_header.machine;
+            let arch = match machine {
+                PE_MACHINE_AMD64 => Some("x86_64"),
+                PE_MACHINE_I386 => Some("i686"),
+                PE_MACHINE_ARM64 => Some("aarch64"),
+                _ => None,
+            };
+            libraries.extend(pe.libraries.iter().map(|l| l.to_string()));
+            (arch, if pe.is_64 { 64 } else { 32 }, "windows", format!("PE machine {:#x}", machine))
+        }
+        Ok(Object::Mach(goblin::mach::Mach::Binary(macho))) => {
+            let arch = match macho.header.cputype {
+                goblin::mach::cputype::CPU_TYPE_X86_64 => Some("x86_64"),
+                goblin::mach::cputype::CPU_TYPE_ARM64 => Some("aarch64"),
+                _ => None,
+            };
+            libraries.extend(macho.libs.iter().filter(|l| **l != "self").map(|l| l.to_string()));
+            (arch, if macho.is_64 { 64 } else { 32 }, "macos", format!("Mach-O CPU type {:#x}", macho.header.cputype))
+        }
+        _ if content.starts_with(b"\0asm") => (Some("wasm32"), 32, "unknown", "WebAssembly module".to_string()),
+        _ => return libraries,
+    };
+    target.bits = Some(bits);
+    if let Some(arch) = arch {
+        target.arch = Some(arch.to_string());
+        target.record("arch", arch, source.clone());
+    }
+    target.os = Some(os.to_string());
+    target.record("os", os, source);
+    libraries
+}
+
+/// Refine a generic OS with std modules only built for a more specific one.
+fn refine_os<'a>(framework_paths: impl IntoIterator<Item = &'a String>, content: &[u8], target: &mut TargetInfo) {
+    let paths: HashSet<String> = framework_paths.into_iter().map(|p| p.replace('\\', "/")).collect();
+    let found: Vec<(&str, &str)> = OS_MODULES
+        .iter()
+        .copied()
+        .filter(|(fragment, _)| paths.iter().any(|p| p.contains(fragment)))
+        .collect();
+    let current = target.os.clone().unwrap_or_default();
+    let refined = found.iter().find(|(_, os)| {
+        matches!(
+            (current.as_str(), *os),
+            ("linux", "android" | "freebsd" | "netbsd" | "openbsd") | ("macos", "ios") | ("unknown", "wasi")
+        )
+    });
+    if let Some((fragment, os)) = refined {
+        target.os = Some(os.to_string());
+        target.record("os", os, format!("std path {}", fragment));
+    } else if current == "unknown" && !find_needles(content, &[b"wasi_snapshot_preview1"]).is_empty() {
+        target.os = Some("wasi".to_string());
+        target.record("os", "wasi", "wasi_snapshot_preview1 import".to_string());
+    }
+}
+
+/// The linker only writes a Rich header with Microsoft's toolchain.
+fn has_rich_header(content: &[u8]) -> bool {
+    let Some(lfanew) = content.get(0x3c..0x40).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize) else {
+        return false;
+    };
+    content.get(0x40..lfanew.min(content.len())).is_some_and(|stub| stub.windows(4).any(|w| w == b"Rich"))
+}
+
+fn detect_env(content: &[u8], libraries: &[String], target: &mut TargetInfo) -> Result<(), Box<dyn std::error::Error>> {
+    let os = target.os.clone().unwrap_or_default();
+    let candidates: &[&str] = match os.as_str() {
+        "linux" => &["gnu", "musl"],
+        "windows" => &["msvc", "gnu"],
+        _ => return Ok(()),
+    };
+    let mut votes: Vec<(&str, String)> = Vec::new();
+    if os == "windows" && has_rich_header(content) {
+        votes.push(("msvc", "PE Rich header".to_string()));
+    }
+    for library in libraries {
+        let lower = library.to_ascii_lowercase();
+        let env = if lower.contains("musl") {
+            "musl"
+        } else if lower.starts_with("vcruntime") {
+            "msvc"
+        } else if lower.contains("libgcc") || lower.contains("ld-linux") || lower == "libc.so.6" {
+            "gnu"
+        } else {
+            continue;
+        };
+        votes.push((env, format!("linked library {}", library)));
+    }
+    for &(env, pattern, what) in ENV_MARKERS {
+        if !candidates.contains(&env) || votes.iter().any(|(e, _)| *e == env) {
+            continue;
+        }
+        if let Some(mat) = Regex::new(pattern)?.find(content) {
+            votes.push((env, format!("{} {}", what, String::from_utf8_lossy(mat.as_bytes()))));
+        }
+    }
+    // musl binaries can still carry glibc-looking strings from vendored C code,
+    // and MinGW builds can link MSVC-built objects, so the rarer runtime wins.
+    let preferred = ["musl", "gnu", "msvc"];
+    let chosen = preferred
+        .iter()
+        .find(|env| candidates.contains(env) && votes.iter().any(|(e, _)| e == *env))
+        .copied()
+        .unwrap_or(candidates[0]);
+    let mut reasons: Vec<String> = votes.into_iter().filter(|(e, _)| *e == chosen).map(|(_, s)| s).collect();
+    if reasons.is_empty() {
+        reasons.push(format!("default for {}", os));
+    }
+    target.env = Some(chosen.to_string());
+    for reason in reasons {
+        target.record("env", chosen, reason);
+    }
+    Ok(())
+}
+
+fn format_triple(arch: &str, os: &str, env: Option<&str>) -> Option<String> {
+    let triple = match (os, env) {
+        ("linux", Some(env)) if arch == "armv7" => format!("{}-unknown-linux-{}eabihf", arch, env),
+        ("linux", Some(env)) if arch.starts_with("mips64") => format!("{}-unknown-linux-{}abi64", arch, env),
+        ("linux", Some(env)) => format!("{}-unknown-linux-{}", arch, env),
+        ("android", _) if arch == "armv7" => "armv7-linux-androideabi".to_string(),
+        ("android", _) => format!("{}-linux-android", arch),
+        ("freebsd", _) => format!("{}-unknown-freebsd", arch),
+        ("netbsd", _) => format!("{}-unknown-netbsd", arch),
+        ("openbsd", _) => format!("{}-unknown-openbsd", arch),
+        ("windows", Some(env)) => format!("{}-pc-windows-{}", arch, env),
+        ("macos", _) => format!("{}-apple-darwin", arch),
+        ("ios", _) => format!("{}-apple-ios", arch),
+        ("wasi", _) => format!("{}-wasip1", arch),
+        ("unknown", _) if arch == "wasm32" => "wasm32-unknown-unknown".to_string(),
+        _ => return None,
+    };
+    Some(triple)
+}
+
+/// Infer architecture and bitness from the container header, the OS from the
+/// container and std `os::` modules, and the C runtime from linked libraries
+/// and runtime artefacts.
+pub fn infer_target<'a>(
+    content: &[u8],
+    framework_paths: impl IntoIterator<Item = &'a String>,
+) -> Result<TargetInfo, Box<dyn std::error::Error>> {
+    let mut target = TargetInfo::default();
+    let libraries = read_header(content, &mut target);
+    if target.os.is_none() {
+        return Ok(target);
+    }
+    refine_os(framework_paths, content, &mut target);
+    detect_env(content, &libraries, &mut target)?;
+    if let (Some(arch), Some(os)) = (&target.arch, &target.os) {
+        target.triple = format_triple(arch, os, target.env.as_deref());
+    }
+    debug!("Inferred target {:?} from {} signals", target.triple, target.evidence.len());
+    Ok(target)
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use log::debug;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NameCount {
+    pub name: String,
+    pub count: usize,
+}
+
+/// Every toolchain that left source paths in the binary.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ToolchainEvidence {
+    /// Distinct commits of `/rustc/<hash>/` paths, most frequent first.
+    pub rustc_hashes: Vec<NameCount>,
+    /// rustup toolchain directories, e.g. `nightly-2024-01-01-x86_64-unknown-linux-gnu`.
+    pub toolchain_dirs: Vec<NameCount>,
+    /// std was compiled from `rust-src` (`-Zbuild-std`) instead of linked
+    /// prebuilt, so its paths are not remapped to `/rustc/<hash>/`.
+    pub build_std: bool,
+    /// More than one rustc commit or toolchain directory contributed code,
+    /// e.g. precompiled objects from another toolchain.
+    pub mixed_toolchains: bool,
+}
+
+impl ToolchainEvidence {
+    /// The hash most source paths point to.
+    pub fn primary_hash(&self) -> Option<&str> {
+        self.rustc_hashes.first().map(|h| h.name.as_str())
+    }
+
+    /// Version named by a toolchain directory such as `nightly-2024-01-01-<target>`
+    /// or `1.75.0-<target>`; channel-only names like `stable-<target>` give none.
+    pub fn toolchain_dir_version(&self) -> Option<String> {
+        let re = regex::Regex::new(r"^((?:nightly|beta)-\d{4}-\d{2}-\d{2}|\d+\.\d+\.\d+)(?:-|$)").ok()?;
+        self.toolchain_dirs
+            .iter()
+            .find_map(|dir| re.captures(&dir.name).and_then(|caps| caps.get(1)).map(|v| v.as_str().to_string()))
+    }
+}
+
+/// Occurrences of each capture, most frequent first, ties in order of first appearance.
+fn count_captures(re: &Regex, content: &[u8]) -> Vec<NameCount> {
+    let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+    for (i, caps) in re.captures_iter(content).enumerate() {
+        let Some(name) = caps.get(1) else { continue };
+        let entry = counts.entry(String::from_utf8_lossy(name.as_bytes()).into_owned()).or_insert((0, i));
+        entry.0 += 1;
+    }
+    let mut counts: Vec<(String, (usize, usize))> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.1 .1.cmp(&b.1 .1)));
+    counts.into_iter().map(|(name, (count, _))| NameCount { name, count }).collect()
+}
+
+pub fn collect_toolchain_evidence(content: &[u8]) -> Result<ToolchainEvidence, Box<dyn std::error::Error>> {
+    let hash_re = Regex::new(r"[\\/]rustc[\\/]([a-f0-9]{40})[\\/]")?;
+    let toolchain_re = Regex::new(r"[\\/]\.?rustup[\\/]toolchains[\\/]([A-Za-z0-9._\-]+)[\\/]")?;
+    let rust_src_re = Regex::new(r"[\\/]toolchains[\\/][A-Za-z0-9._\-]+[\\/]lib[\\/]rustlib[\\/]src[\\/]rust[\\/]library[\\/]")?;
+
+    let rustc_hashes = count_captures(&hash_re, content);
+    let toolchain_dirs = count_captures(&toolchain_re, content);
+    let build_std = rust_src_re.is_match(content);
+    let mixed_toolchains = rustc_hashes.len() > 1 || toolchain_dirs.len() > 1;
+    debug!(
+        "{} rustc hashes, {} toolchain directories, build_std: {}",
+        rustc_hashes.len(),
+        toolchain_dirs.len(),
+        build_std
+    );
+    Ok(ToolchainEvidence { rustc_hashes, toolchain_dirs, build_std, mixed_toolchains })
+}
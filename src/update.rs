@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::fs;
-use log::{info, debug};
+use std::path::Path;
+use log::{info, debug, warn};
+use crate::versions::{format_date, llvm_major, parse_date, ToolchainInfo, VersionDatabase, SCHEMA_VERSION, VERSIONS_FILE};
 
 // GitHub API response structures
 #[derive(serde::Deserialize, Debug)]
@@ -50,22 +52,34 @@ async fn fetch_github_tags(owner: &str, repo: &str) -> Result<Vec<GitHubTag>, Bo
     Ok(tags)
 }
 
-fn process_tags(tags: Vec<GitHubTag>) -> HashMap<String, String> {
-    let mut hash_to_version = HashMap::new();
+fn process_tags(tags: Vec<GitHubTag>) -> VersionDatabase {
+    let mut database = VersionDatabase::default();
     
     for tag in tags {
-        // Store the mapping: commit hash -> version tag
-        hash_to_version.insert(tag.commit.sha.clone(), tag.name.clone());
+        // Store the mapping: commit hash -> version tag and derived metadata
         debug!("  {} -> {}", tag.name, tag.commit.sha);
+        database.insert(tag.commit.sha, ToolchainInfo::from_tag(&tag.name));
     }
     
-    hash_to_version
+    database
+}
+
+/// Merge `imported` into the database file, keeping entries it lacks such as
+/// imported nightly and beta builds. Returns the total entry count.
+fn merge_into_database(imported: VersionDatabase) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut database = match fs::read_to_string(VERSIONS_FILE) {
+        Ok(content) => VersionDatabase::parse(&content)?,
+        Err(_) => VersionDatabase::default(),
+    };
+    database.schema_version = SCHEMA_VERSION;
+    database.merge(imported);
+    fs::write(VERSIONS_FILE, serde_json::to_string_pretty(&database)?)?;
+    Ok(database.toolchains.len())
 }
 
 pub async fn update_rust_versions() -> Result<(), Box<dyn std::error::Error>> {
     let owner = "rust-lang";
     let repo = "rust";
-    let output_file = "rust_versions.json";
     
     info!("Fetching all tags from {}/{}...", owner, repo);
     
@@ -75,15 +89,159 @@ pub async fn update_rust_versions() -> Result<(), Box<dyn std::error::Error>> {
     info!("Found {} tags", tags.len());
     info!("Processing tags...");
     
-    // Process tags into hash -> version mapping
-    let hash_to_version = process_tags(tags);
+    // Process tags into hash -> toolchain entries
+    let database = process_tags(tags);
     
-    // Save to JSON file
-    let json_content = serde_json::to_string_pretty(&hash_to_version)?;
-    fs::write(output_file, json_content)?;
+    // Merge into the JSON file
+    let total = merge_into_database(database)?;
     
-    info!("Successfully saved {} version mappings to {}", hash_to_version.len(), output_file);
+    info!("Successfully saved {} version mappings to {}", total, VERSIONS_FILE);
     info!("Done!");
     
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct ChannelManifest {
+    date: String,
+    pkg: HashMap<String, ManifestPackage>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct ManifestPackage {
+    version: String,
+    git_commit_hash: Option<String>,
+}
+
+/// Read the rustc commit from a `channel-rust-*.toml` manifest. Its version
+/// reads like `1.80.0-nightly (9c9b56879 2024-05-02)`; the manifest date
+/// names the nightly toolchain and the parenthesized one is the commit's.
+pub fn parse_channel_manifest(content: &str) -> Result<(String, ToolchainInfo), Box<dyn std::error::Error>> {
+    let manifest: ChannelManifest = toml::from_str(content)?;
+    let rustc = manifest
+        .pkg
+        .get("rustc")
+        .or_else(|| manifest.pkg.get("rust"))
+        .ok_or("manifest has no rustc package")?;
+    let hash = rustc.git_commit_hash.clone().ok_or("manifest has no rustc commit hash")?;
+
+    let mut parts = rustc.version.split_whitespace();
+    let release = parts.next().ok_or("empty rustc version")?;
+    let commit_date = parts
+        .last()
+        .map(|date| date.trim_end_matches(')'))
+        .filter(|date| date.len() == 10)
+        .unwrap_or(&manifest.date)
+        .to_string();
+    let (version, channel) = if release.contains("-nightly") {
+        (format!("nightly-{}", manifest.date), "nightly")
+    } else if release.contains("-beta") {
+        (release.to_string(), "beta")
+    } else {
+        (release.to_string(), "stable")
+    };
+    debug!("  {} -> {} ({})", version, hash, commit_date);
+    let info = ToolchainInfo {
+        version,
+        channel: channel.to_string(),
+        date: Some(commit_date),
+        llvm_major: llvm_major(release),
+        tag: None,
+    };
+    Ok((hash, info))
+}
+
+fn is_channel_manifest(name: &str) -> bool {
+    name.starts_with("channel-rust-") && name.ends_with(".toml")
+}
+
+/// Import every `channel-rust-*.toml` below a local mirror of `dist/`.
+pub fn import_channel_dir(dir: &Path) -> Result<VersionDatabase, Box<dyn std::error::Error>> {
+    let mut builds = VersionDatabase::default();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.file_name().and_then(|n| n.to_str()).is_some_and(is_channel_manifest) {
+                match fs::read_to_string(&path).map_err(|e| e.into()).and_then(|c| parse_channel_manifest(&c)) {
+                    Ok((hash, info)) => builds.insert(hash, info),
+                    Err(e) => warn!("Skipping {}: {}", path.display(), e),
+                }
+            }
+        }
+    }
+    Ok(builds)
+}
+
+/// Fetch the current manifests from `base_url`, plus the dated archives
+/// `<base_url>/<date>/channel-rust-*.toml` from `since` through today.
+async fn fetch_channel_manifests(base_url: &str, since: Option<&str>) -> Result<VersionDatabase, Box<dyn std::error::Error>> {
+    let client = reqwest::Client::new();
+    let base_url = base_url.trim_end_matches('/');
+    let mut urls = Vec::new();
+    if let Some(since) = since {
+        let today = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs() as i64 / 86400;
+        for days in parse_date(since)?..=today {
+            for channel in ["nightly", "beta"] {
+                urls.push(format!("{}/{}/channel-rust-{}.toml", base_url, format_date(days), channel));
+            }
+        }
+    }
+    for channel in ["nightly", "beta"] {
+        urls.push(format!("{}/channel-rust-{}.toml", base_url, channel));
+    }
+
+    // Dated manifests are missing for many days, so a 404 is expected and
+    // any other failure only loses that one manifest.
+    let mut builds = VersionDatabase::default();
+    let mut fetched = 0;
+    for url in urls {
+        let response = match client.get(&url).header("User-Agent", "rustre").send().await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Failed to fetch {}: {}", url, e);
+                continue;
+            }
+        };
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            debug!("No manifest at {}", url);
+            continue;
+        }
+        if !response.status().is_success() {
+            warn!("Failed to fetch {}: {}", url, response.status());
+            continue;
+        }
+        let content = match response.text().await {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Failed to read {}: {}", url, e);
+                continue;
+            }
+        };
+        fetched += 1;
+        match parse_channel_manifest(&content) {
+            Ok((hash, info)) => builds.insert(hash, info),
+            Err(e) => warn!("Skipping {}: {}", url, e),
+        }
+    }
+    if fetched == 0 {
+        return Err(format!("no channel manifest could be fetched from {}", base_url).into());
+    }
+    Ok(builds)
+}
+
+/// Import nightly and beta builds from a local mirror directory or a served
+/// `dist/` stand-in and merge them into `rust_versions.json`.
+pub async fn update_channel_builds(source: &str, since: Option<&str>) -> Result<usize, Box<dyn std::error::Error>> {
+    let imported = if source.starts_with("http://") || source.starts_with("https://") {
+        fetch_channel_manifests(source, since).await?
+    } else {
+        import_channel_dir(Path::new(source))?
+    };
+    let count = imported.toolchains.len();
+    let total = merge_into_database(imported)?;
+    info!("Saved {} version mappings to {}", total, VERSIONS_FILE);
+    Ok(count)
+}
@@ -0,0 +1,211 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use log::debug;
+use crate::{AnalysisResult, STD_DEPENDENCY_CRATES};
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DeclaredCrate {
+    pub name: String,
+    /// `None` when an SBOM lists the crate without a version.
+    pub version: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BinaryCrate {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct VersionMismatch {
+    pub name: String,
+    pub binary_versions: Vec<String>,
+    pub declared_versions: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct VerifyReport {
+    pub declared_source: String,
+    /// Crates evidenced in the binary but absent from the declared set.
+    pub undeclared: Vec<BinaryCrate>,
+    /// Crates present in both whose binary versions are not all declared.
+    pub version_mismatches: Vec<VersionMismatch>,
+    /// Declared crates with no trace in the binary. Proc-macros, build
+    /// dependencies and fully inlined crates commonly end up here.
+    pub not_evidenced: Vec<DeclaredCrate>,
+    /// Undeclared crates pulled in by the standard library itself.
+    pub toolchain_crates: Vec<BinaryCrate>,
+    pub passed: bool,
+}
+
+#[derive(Deserialize)]
+struct CargoLock {
+    #[serde(default)]
+    package: Vec<LockPackage>,
+}
+
+#[derive(Deserialize)]
+struct LockPackage {
+    name: String,
+    version: String,
+    source: Option<String>,
+}
+
+/// Parse a Cargo.lock, keeping only registry packages: path and git
+/// dependencies never appear under `.cargo/registry/src`.
+fn parse_cargo_lock(content: &str) -> Result<Vec<DeclaredCrate>, Box<dyn std::error::Error>> {
+    let lock: CargoLock = toml::from_str(content)?;
+    Ok(lock
+        .package
+        .into_iter()
+        .filter(|p| {
+            p.source
+                .as_deref()
+                .is_some_and(|s| s.starts_with("registry+") || s.starts_with("sparse+"))
+        })
+        .map(|p| DeclaredCrate { name: p.name, version: Some(p.version) })
+        .collect())
+}
+
+/// Split a `pkg:cargo/<name>@<version>` purl into its crate name and version.
+fn parse_cargo_purl(purl: &str) -> Option<DeclaredCrate> {
+    let rest = purl.strip_prefix("pkg:cargo/")?;
+    let rest = rest.split(['?', '#']).next()?;
+    Some(match rest.rsplit_once('@') {
+        Some((name, version)) => DeclaredCrate { name: name.to_string(), version: Some(version.to_string()) },
+        None => DeclaredCrate { name: rest.to_string(), version: None },
+    })
+}
+
+fn parse_cyclonedx(doc: &Value) -> Vec<DeclaredCrate> {
+    let mut crates = Vec::new();
+    let mut stack: Vec<&Value> = vec![&doc["components"]];
+    while let Some(components) = stack.pop() {
+        for component in components.as_array().into_iter().flatten() {
+            if let Some(declared) = component["purl"].as_str().and_then(parse_cargo_purl) {
+                crates.push(declared);
+            }
+            stack.push(&component["components"]);
+        }
+    }
+    crates
+}
+
+fn parse_spdx(doc: &Value) -> Vec<DeclaredCrate> {
+    doc["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|p| p["externalRefs"].as_array().into_iter().flatten())
+        .filter(|r| r["referenceType"] == "purl")
+        .filter_map(|r| r["referenceLocator"].as_str().and_then(parse_cargo_purl))
+        .collect()
+}
+
+/// Load the declared dependency set from a Cargo.lock, a CycloneDX JSON BOM
+/// or an SPDX JSON document.
+pub fn load_declared_crates(path: &str) -> Result<Vec<DeclaredCrate>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)?;
+    let crates = if content.trim_start().starts_with('{') {
+        let doc: Value = serde_json::from_str(&content)?;
+        if doc.get("bomFormat").is_some() {
+            parse_cyclonedx(&doc)
+        } else if doc.get("spdxVersion").is_some() {
+            parse_spdx(&doc)
+        } else {
+            return Err(format!("{} is neither a CycloneDX nor an SPDX document", path).into());
+        }
+    } else {
+        parse_cargo_lock(&content)?
+    };
+    debug!("Loaded {} declared crates from {}", crates.len(), path);
+    Ok(crates)
+}
+
+pub fn verify_packages(result: &AnalysisResult, declared: &[DeclaredCrate], declared_source: &str) -> VerifyReport {
+    let mut declared_by_name: BTreeMap<&str, BTreeSet<Option<&str>>> = BTreeMap::new();
+    for krate in declared {
+        declared_by_name.entry(&krate.name).or_default().insert(krate.version.as_deref());
+    }
+
+    // `None` marks a crate whose version could not be read from its path.
+    let mut binary_by_name: BTreeMap<String, BTreeSet<Option<String>>> = BTreeMap::new();
+    for package in &result.packages {
+        let (name, version) = package.crate_name_and_version();
+        binary_by_name.entry(name).or_default().insert(version);
+    }
+
+    let mut undeclared = Vec::new();
+    let mut version_mismatches = Vec::new();
+    let mut toolchain_crates = Vec::new();
+
+    for (name, versions) in &binary_by_name {
+        match declared_by_name.get(name.as_str()) {
+            None => {
+                let crates = versions.iter().map(|v| BinaryCrate { name: name.clone(), version: v.clone() });
+                if STD_DEPENDENCY_CRATES.contains(&name.as_str()) {
+                    toolchain_crates.extend(crates);
+                } else {
+                    undeclared.extend(crates);
+                }
+            }
+            Some(declared_versions) => {
+                // An unversioned declaration accepts whatever the binary has.
+                if declared_versions.contains(&None) {
+                    continue;
+                }
+                let unexpected: Vec<String> = versions
+                    .iter()
+                    .flatten()
+                    .filter(|v| !declared_versions.contains(&Some(v.as_str())))
+                    .cloned()
+                    .collect();
+                if unexpected.is_empty() {
+                    continue;
+                }
+                if STD_DEPENDENCY_CRATES.contains(&name.as_str()) {
+                    // The program and std may each link their own copy.
+                    toolchain_crates.extend(
+                        unexpected.into_iter().map(|v| BinaryCrate { name: name.clone(), version: Some(v) }),
+                    );
+                } else {
+                    version_mismatches.push(VersionMismatch {
+                        name: name.clone(),
+                        binary_versions: versions.iter().flatten().cloned().collect(),
+                        declared_versions: declared_versions.iter().flatten().map(|v| v.to_string()).collect(),
+                    });
+                }
+            }
+        }
+    }
+
+    let mut not_evidenced: Vec<DeclaredCrate> = declared
+        .iter()
+        .filter(|krate| !binary_by_name.contains_key(&krate.name))
+        .cloned()
+        .collect();
+    not_evidenced.sort();
+    not_evidenced.dedup();
+
+    let passed = undeclared.is_empty() && version_mismatches.is_empty();
+    debug!(
+        "Verification: {} undeclared, {} version mismatches, {} not evidenced",
+        undeclared.len(), version_mismatches.len(), not_evidenced.len()
+    );
+
+    VerifyReport {
+        declared_source: declared_source.to_string(),
+        undeclared,
+        version_mismatches,
+        not_evidenced,
+        toolchain_crates,
+        passed,
+    }
+}
+
+pub fn verify(result: &AnalysisResult, declared_path: &str) -> Result<VerifyReport, Box<dyn std::error::Error>> {
+    let declared = load_declared_crates(declared_path)?;
+    Ok(verify_packages(result, &declared, declared_path))
+}
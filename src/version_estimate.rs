@@ -0,0 +1,154 @@
+use std::sync::OnceLock;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use log::debug;
+use crate::{find_needles, Package};
+
+/// Minimum supported Rust versions of common crates, maintained in `crate_msrv.json`.
+const CRATE_MSRV: &str = include_str!("../crate_msrv.json");
+
+/// std source paths that only exist in some releases, as
+/// `(fragment, first minor, last minor)` of Rust 1.x.
+const STD_PATH_RULES: &[(&str, Option<u32>, Option<u32>)] = &[
+    // std moved from `src/libstd` to `library/std` in 1.47.
+    ("src/libstd/", None, Some(46)),
+    ("src/libcore/", None, Some(46)),
+    ("src/liballoc/", None, Some(46)),
+    ("library/std/src/", Some(47), None),
+    ("library/core/src/", Some(47), None),
+    ("library/alloc/src/", Some(47), None),
+    // mpsc was reimplemented on the crossbeam-based `mpmc` module.
+    ("library/std/src/sync/mpmc/", Some(67), None),
+    ("library/std/src/sync/mpsc/oneshot.rs", None, Some(66)),
+    ("library/std/src/sync/mpsc/shared.rs", None, Some(66)),
+    ("library/std/src/sync/mpsc/stream.rs", None, Some(66)),
+    // Platform code moved under `sys/pal`.
+    ("library/std/src/sys/pal/", Some(77), None),
+    ("library/std/src/sys/unix/", None, Some(76)),
+    ("library/std/src/sys/windows/", None, Some(76)),
+    // driftsort and ipnsort replaced `slice/sort.rs`.
+    ("library/core/src/slice/sort/", Some(81), None),
+    ("library/core/src/slice/sort.rs", None, Some(80)),
+    ("library/std/src/sync/poison/", Some(84), None),
+];
+
+/// Pieces of the default panic hook message, which 1.73 changed from
+/// `panicked at 'msg', file:line:col` to `panicked at file:line:col:\nmsg`.
+const PANIC_FORMAT_RULES: &[(&[u8], Option<u32>, Option<u32>)] = &[
+    (b"panicked at '', ", None, Some(72)),
+    (b"panicked at :\n", Some(73), None),
+];
+
+#[derive(Deserialize, Debug)]
+struct CrateMsrv {
+    #[serde(rename = "crate")]
+    name: String,
+    since: String,
+    rust: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VersionBound {
+    /// `std_path`, `panic_format` or `crate_msrv`.
+    pub signal: String,
+    pub evidence: String,
+    pub min: Option<String>,
+    pub max: Option<String>,
+}
+
+/// Range of Rust releases consistent with the binary, for when no commit
+/// hash or version record resolves it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VersionEstimate {
+    /// Oldest possible release, e.g. `1.67`.
+    pub min: Option<String>,
+    /// Newest possible release.
+    pub max: Option<String>,
+    /// False when the signals contradict each other, e.g. a mixed toolchain.
+    pub consistent: bool,
+    pub reasons: Vec<VersionBound>,
+}
+
+fn minor(version: &str) -> Option<u32> {
+    let mut parts = version.split('.');
+    (parts.next()? == "1").then_some(())?;
+    parts.next()?.parse().ok()
+}
+
+fn release(minor: u32) -> String {
+    format!("1.{}", minor)
+}
+
+fn bound(signal: &str, evidence: String, min: Option<u32>, max: Option<u32>) -> VersionBound {
+    VersionBound { signal: signal.to_string(), evidence, min: min.map(release), max: max.map(release) }
+}
+
+/// The embedded table, parsed on first use.
+fn load_msrv_table() -> Result<&'static [CrateMsrv], Box<dyn std::error::Error>> {
+    static TABLE: OnceLock<Result<Vec<CrateMsrv>, String>> = OnceLock::new();
+    TABLE
+        .get_or_init(|| serde_json::from_str(CRATE_MSRV).map_err(|e| e.to_string()))
+        .as_deref()
+        .map_err(|e| format!("invalid embedded crate_msrv.json: {}", e).into())
+}
+
+fn msrv_bounds(packages: &[Package]) -> Result<Vec<VersionBound>, Box<dyn std::error::Error>> {
+    let rules = load_msrv_table()?;
+    let mut bounds = Vec::new();
+    for package in packages {
+        let (name, Some(version)) = package.crate_name_and_version() else { continue };
+        let Ok(version) = Version::parse(&version) else { continue };
+        // The newest threshold the linked version reaches decides its MSRV.
+        let msrv = rules
+            .iter()
+            .filter(|r| r.name == name && Version::parse(&r.since).is_ok_and(|since| version >= since))
+            .filter_map(|r| minor(&r.rust))
+            .max();
+        if let Some(msrv) = msrv {
+            bounds.push(bound("crate_msrv", format!("{}@{} requires Rust {}", name, version, release(msrv)), Some(msrv), None));
+        }
+    }
+    Ok(bounds)
+}
+
+/// Combine std source layout, version-specific std files, the panic message
+/// format and crate MSRVs into a version range. `None` without any signal.
+pub fn estimate_rust_version(
+    content: &[u8],
+    packages: &[Package],
+) -> Result<Option<VersionEstimate>, Box<dyn std::error::Error>> {
+    // Windows builds embed std paths with backslashes.
+    let backslashed: Vec<String> = STD_PATH_RULES.iter().map(|(fragment, _, _)| fragment.replace('/', "\\")).collect();
+    let needles: Vec<&[u8]> = STD_PATH_RULES
+        .iter()
+        .map(|(fragment, _, _)| fragment.as_bytes())
+        .chain(backslashed.iter().map(|fragment| fragment.as_bytes()))
+        .chain(PANIC_FORMAT_RULES.iter().map(|(piece, _, _)| *piece))
+        .collect();
+    let found = find_needles(content, &needles);
+    let contains = |needle: &[u8]| found.contains(needle);
+    let mut reasons = Vec::new();
+
+    for (&(fragment, min, max), backslashed) in STD_PATH_RULES.iter().zip(&backslashed) {
+        if contains(fragment.as_bytes()) || contains(backslashed.as_bytes()) {
+            reasons.push(bound("std_path", fragment.to_string(), min, max));
+        }
+    }
+    for &(piece, min, max) in PANIC_FORMAT_RULES {
+        if contains(piece) {
+            reasons.push(bound("panic_format", format!("{:?}", String::from_utf8_lossy(piece)), min, max));
+        }
+    }
+    let mut msrv = msrv_bounds(packages)?;
+    msrv.sort_by(|a, b| b.min.as_deref().and_then(minor).cmp(&a.min.as_deref().and_then(minor)).then(a.evidence.cmp(&b.evidence)));
+    reasons.extend(msrv);
+
+    if reasons.is_empty() {
+        return Ok(None);
+    }
+    let min = reasons.iter().filter_map(|r| r.min.as_deref().and_then(minor)).max();
+    let max = reasons.iter().filter_map(|r| r.max.as_deref().and_then(minor)).min();
+    let consistent = !matches!((min, max), (Some(min), Some(max)) if min > max);
+    debug!("Estimated Rust version range {:?}..={:?} from {} signals", min, max, reasons.len());
+    Ok(Some(VersionEstimate { min: min.map(release), max: max.map(release), consistent, reasons }))
+}
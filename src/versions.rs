@@ -0,0 +1,294 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use serde::{Deserialize, Serialize};
+use log::{debug, warn};
+
+pub const VERSIONS_FILE: &str = "rust_versions.json";
+pub const SCHEMA_VERSION: u32 = 2;
+/// Shortest abbreviated commit hash looked up by prefix, as in `git log --oneline`.
+pub const MIN_HASH_PREFIX_LEN: usize = 7;
+
+/// First stable release bundling each LLVM major version.
+const LLVM_MAJORS: &[(u32, u32)] = &[
+    (47, 11),
+    (52, 12),
+    (56, 13),
+    (60, 14),
+    (65, 15),
+    (70, 16),
+    (73, 17),
+    (78, 18),
+    (82, 19),
+    (87, 20),
+    (91, 21),
+    (95, 22),
+];
+/// Newest minor release `LLVM_MAJORS` is known to cover; later ones may have moved on.
+const LLVM_MAJORS_UNTIL: u32 = 97;
+
+/// Metadata of the toolchain behind one rustc commit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ToolchainInfo {
+    /// `1.77.0`, `1.79.0-beta.4` or `nightly-2024-05-03`.
+    pub version: String,
+    /// `stable`, `beta`, `nightly`, or `pre-release` for tags before 1.0.
+    pub channel: String,
+    /// Release date of a stable `x.y.0`, commit date otherwise, `YYYY-MM-DD`.
+    #[serde(default)]
+    pub date: Option<String>,
+    #[serde(default)]
+    pub llvm_major: Option<u32>,
+    /// Git tag of a tagged release.
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+impl ToolchainInfo {
+    /// Entry for a tag of rust-lang/rust, with metadata derived from the version.
+    pub fn from_tag(tag: &str) -> Self {
+        let channel = if is_release(tag) {
+            "stable"
+        } else if tag.contains("beta") {
+            "beta"
+        } else {
+            "pre-release"
+        };
+        ToolchainInfo {
+            version: tag.to_string(),
+            channel: channel.to_string(),
+            date: if channel == "stable" { stable_release_date(tag) } else { None },
+            llvm_major: llvm_major(tag),
+            tag: Some(tag.to_string()),
+        }
+    }
+
+    /// One-line description, e.g. `rustc 1.77.0 (stable) released 2024-03-21 bundling LLVM 17`.
+    pub fn describe(&self) -> String {
+        let mut out = format!("rustc {} ({})", self.version, self.channel);
+        if let Some(date) = &self.date {
+            let verb = if self.channel == "stable" { "released" } else { "committed" };
+            out.push_str(&format!(" {} {}", verb, date));
+        }
+        if let Some(llvm) = self.llvm_major {
+            out.push_str(&format!(" bundling LLVM {}", llvm));
+        }
+        out
+    }
+
+    fn rank(&self) -> u8 {
+        match self.channel.as_str() {
+            "stable" => 0,
+            "beta" => 1,
+            "nightly" => 2,
+            _ => 3,
+        }
+    }
+}
+
+/// Result of looking up a full or abbreviated commit hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HashLookup<'a> {
+    /// The full hash and its entry.
+    Found(&'a str, &'a ToolchainInfo),
+    /// Full hashes of every commit the prefix matches.
+    Ambiguous(Vec<&'a str>),
+    NotFound,
+}
+
+/// Versioned `rust_versions.json`: rustc commit hash to toolchain metadata.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VersionDatabase {
+    pub schema_version: u32,
+    pub toolchains: BTreeMap<String, ToolchainInfo>,
+}
+
+impl Default for VersionDatabase {
+    fn default() -> Self {
+        VersionDatabase { schema_version: SCHEMA_VERSION, toolchains: BTreeMap::new() }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DatabaseFile {
+    Versioned(VersionDatabase),
+    /// The original format: commit hash to tag name.
+    Flat(HashMap<String, String>),
+}
+
+impl VersionDatabase {
+    /// Parse either schema; flat files are migrated in memory.
+    pub fn parse(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        match serde_json::from_str::<DatabaseFile>(content)? {
+            DatabaseFile::Versioned(database) if database.schema_version > SCHEMA_VERSION => Err(format!(
+                "unsupported schema version {} (newest known is {})",
+                database.schema_version, SCHEMA_VERSION
+            )
+            .into()),
+            DatabaseFile::Versioned(database) => Ok(database),
+            DatabaseFile::Flat(tags) => {
+                debug!("Migrating flat version database with {} entries", tags.len());
+                let mut database = VersionDatabase::default();
+                for (hash, tag) in tags {
+                    database.insert(hash, ToolchainInfo::from_tag(&tag));
+                }
+                Ok(database)
+            }
+        }
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&ToolchainInfo> {
+        self.toolchains.get(hash)
+    }
+
+    /// Look up a full hash, or a unique prefix of at least `MIN_HASH_PREFIX_LEN` digits.
+    pub fn lookup(&self, hash: &str) -> HashLookup<'_> {
+        let hash = hash.to_ascii_lowercase();
+        if let Some((full, info)) = self.toolchains.get_key_value(&hash) {
+            return HashLookup::Found(full, info);
+        }
+        if hash.len() < MIN_HASH_PREFIX_LEN {
+            return HashLookup::NotFound;
+        }
+        let matches: Vec<(&String, &ToolchainInfo)> = self
+            .toolchains
+            .range(hash.clone()..)
+            .take_while(|(full, _)| full.starts_with(&hash))
+            .collect();
+        match matches.as_slice() {
+            [] => HashLookup::NotFound,
+            [(full, info)] => HashLookup::Found(full, info),
+            _ => HashLookup::Ambiguous(matches.iter().map(|(full, _)| full.as_str()).collect()),
+        }
+    }
+
+    /// Add an entry. A commit shipped on several channels keeps the most
+    /// stable one, and on one channel the earliest build.
+    pub fn insert(&mut self, hash: String, info: ToolchainInfo) {
+        match self.toolchains.get(&hash) {
+            Some(existing) if (existing.rank(), &existing.version) <= (info.rank(), &info.version) => {}
+            _ => {
+                self.toolchains.insert(hash, info);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: VersionDatabase) {
+        for (hash, info) in other.toolchains {
+            self.insert(hash, info);
+        }
+    }
+
+    /// Commit hash to version string, the shape of the original file.
+    pub fn mappings(&self) -> HashMap<String, String> {
+        self.toolchains.iter().map(|(hash, info)| (hash.clone(), info.version.clone())).collect()
+    }
+}
+
+pub fn load_version_database() -> Option<VersionDatabase> {
+    match fs::read_to_string(VERSIONS_FILE) {
+        Ok(content) => match VersionDatabase::parse(&content) {
+            Ok(database) => Some(database),
+            Err(e) => {
+                warn!("Failed to parse {}: {}", VERSIONS_FILE, e);
+                None
+            }
+        },
+        Err(e) => {
+            warn!("Failed to read {}: {}", VERSIONS_FILE, e);
+            None
+        }
+    }
+}
+
+fn is_release(version: &str) -> bool {
+    let parts: Vec<&str> = version.split('.').collect();
+    parts.len() == 3 && parts.iter().all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// `(major, minor)` of `1.79.0`, `1.79.0-beta.4` or `1.80.0-nightly`.
+fn major_minor(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split(['.', '-']);
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+/// LLVM major version bundled with a 1.x release.
+pub fn llvm_major(version: &str) -> Option<u32> {
+    let (major, minor) = major_minor(version)?;
+    if major != 1 || minor > LLVM_MAJORS_UNTIL {
+        return None;
+    }
+    LLVM_MAJORS.iter().rev().find(|(first, _)| minor >= *first).map(|(_, llvm)| *llvm)
+}
+
+/// Release date of a stable `1.y.0`. Releases have shipped every six
+/// weeks since 1.1.0 on 2015-06-25; point releases have no fixed date.
+pub fn stable_release_date(version: &str) -> Option<String> {
+    if !is_release(version) || !version.ends_with(".0") {
+        return None;
+    }
+    match major_minor(version)? {
+        (1, 0) => Some("2015-05-15".to_string()),
+        (1, minor) => Some(format_date(days_from_civil(2015, 6, 25) + (i64::from(minor) - 1) * 42)),
+        _ => None,
+    }
+}
+
+/// The `1.y.0` release a toolchain version ships as: `1.79.0-beta.4` and
+/// `1.79.0-nightly` become `1.79.0`, and a dated `nightly-2024-05-03` or
+/// `beta-2024-05-03` the release its channel was building that day.
+pub fn release_version(version: &str) -> Option<String> {
+    if is_release(version) {
+        return Some(version.to_string());
+    }
+    let (ahead, date) = if let Some(date) = version.strip_prefix("nightly-") {
+        (2, date)
+    } else if let Some(date) = version.strip_prefix("beta-") {
+        (1, date)
+    } else {
+        let (major, minor) = major_minor(version)?;
+        return Some(format!("{}.{}.0", major, minor));
+    };
+    let days = parse_date(date).ok()? - days_from_civil(2015, 6, 25);
+    if days < 0 {
+        return None;
+    }
+    // Beta is one release ahead of the latest stable, nightly two.
+    Some(format!("1.{}.0", days / 42 + 1 + ahead))
+}
+
+/// Days since 1970-01-01 to a proleptic Gregorian date.
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+pub(crate) fn parse_date(date: &str) -> Result<i64, Box<dyn std::error::Error>> {
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(format!("invalid date {:?}, expected YYYY-MM-DD", date).into());
+    };
+    Ok(days_from_civil(year.parse()?, month.parse()?, day.parse()?))
+}
+
+pub(crate) fn format_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,285 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use regex::Regex;
+use crate::{AnalysisResult, STD_DEPENDENCY_CRATES};
+
+/// Most language strings kept per rule.
+const MAX_LANGUAGE_STRINGS: usize = 15;
+/// Most source paths kept per rule.
+const MAX_SOURCE_PATHS: usize = 10;
+/// Crates listed in the rule metadata.
+const MAX_META_CRATES: usize = 10;
+const MIN_STRING_LEN: usize = 8;
+const MAX_STRING_LEN: usize = 200;
+/// PE, ELF, 32- and 64-bit Mach-O, and universal Mach-O headers.
+const EXECUTABLE_MAGIC: &str = "(uint16(0) == 0x5A4D or uint32(0) == 0x464C457F or uint32(0) == 0xFEEDFACE or uint32(0) == 0xFEEDFACF or uint32be(0) == 0xCAFEBABE)";
+
+/// Home directory names of hosted CI runners and containers, shared by
+/// unrelated builds and useless as indicators.
+const GENERIC_USERS: &[&str] = &["runner", "runneradmin", "vssadministrator", "root", "builder"];
+
+/// Fragments of strings that std, core and the MSVC runtime put in every
+/// binary, along with demangler noise and registry paths. Dependency strings
+/// are left to the baseline's `known_library` flag.
+const COMMON_FRAGMENTS: &[&str] = &[
+    "panicked",
+    "called `",
+    "unwrap()",
+    "assertion failed",
+    "internal error: entered unreachable code",
+    "index out of bounds",
+    "capacity overflow",
+    "memory allocation of",
+    "already borrowed",
+    "already mutably borrowed",
+    "RUST_BACKTRACE",
+    "stack backtrace",
+    "fatal runtime error",
+    "formatter error",
+    "a formatting trait implementation",
+    "is not a char boundary",
+    "(os error ",
+    "entity not found",
+    "operation failed to complete synchronously",
+    "thread '",
+    "<unnamed>",
+    "cannot access a Thread Local Storage value",
+    "RustBacktraceMutex",
+    "console mode does not support",
+    "preferred RNG",
+    "from_str_radix",
+    "PosOverflow",
+    "InvalidDigit",
+    "Utf8Error",
+    "program not found",
+    "Windows file names may not contain",
+    "/e:ON /v:OFF",
+    "description() is deprecated",
+    "UTF-8 codepoint",
+    "unique thread ID",
+    "usizeu128",
+    "fmt::Error",
+    "CString::new",
+    "cannot contain NULs",
+    "std::",
+    "__rust_",
+    "CorExitProcess",
+    "`vftable'",
+    "`vbtable'",
+    "destructor'",
+    "constructor closure'",
+    "__cdecl",
+    "__stdcall",
+    "__fastcall",
+    "__thiscall",
+    "__clrcall",
+    "__based(",
+    "unsafe extern",
+    "{shim",
+    "{closure",
+    "{recursion limit reached}",
+    "library/",
+    "library\\",
+    ".cargo",
+    ".rustup",
+    "/rustc/",
+    "/rust/deps",
+    "crates.io",
+    "github.com-",
+    "registry\\src",
+    "registry/src",
+];
+
+/// YARA text string body: printable ASCII verbatim, the rest as escapes.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'\\' => out.push_str("\\\\"),
+            b'"' => out.push_str("\\\""),
+            b'\n' => out.push_str("\\n"),
+            b'\t' => out.push_str("\\t"),
+            0x20..=0x7e => out.push(b as char),
+            _ => {
+                let _ = write!(out, "\\x{:02X}", b);
+            }
+        }
+    }
+    out
+}
+
+fn is_common(s: &str) -> bool {
+    COMMON_FRAGMENTS.iter().any(|fragment| s.contains(fragment))
+}
+
+/// Rough measure of how distinctive a string is: character variety, with a
+/// bonus for paths, registry keys, URLs, commands and file names.
+fn score(s: &str) -> usize {
+    let distinct = s.bytes().collect::<BTreeSet<u8>>().len().min(30);
+    let lower = s.to_ascii_lowercase();
+    let hints = ["\\", "://", "software\\", "system\\", ".exe", ".dll", ".ps1", "cmd", "powershell"];
+    let bonus = hints.iter().filter(|h| lower.contains(*h)).count() * 10;
+    distinct + bonus
+}
+
+fn select_language_strings(result: &AnalysisResult) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    // Fragments of registry paths cut off before the `.cargo` part.
+    let dependency_path = Regex::new(r"-\d+\.\d+\.\d+[^\\/]*[\\/]src[\\/]")?;
+    let mut candidates: Vec<&str> = result
+        .language_strings
+        .iter()
+        .filter(|s| !s.known_library)
+        .map(|s| s.string.as_str())
+        .filter(|s| (MIN_STRING_LEN..=MAX_STRING_LEN).contains(&s.len()))
+        .filter(|s| s.bytes().filter(|b| b.is_ascii_alphanumeric()).count() * 2 > s.len())
+        .filter(|s| !is_common(s) && !dependency_path.is_match(s))
+        .collect();
+    candidates.sort_by(|a, b| score(b).cmp(&score(a)).then(b.len().cmp(&a.len())).then(a.cmp(b)));
+    candidates.dedup();
+
+    // Extracted strings overlap (suffixes of one blob); keep one per blob.
+    let mut selected: Vec<String> = Vec::new();
+    for candidate in candidates {
+        if selected.iter().any(|s| s.contains(candidate) || candidate.contains(s.as_str())) {
+            continue;
+        }
+        selected.push(candidate.to_string());
+        if selected.len() == MAX_LANGUAGE_STRINGS {
+            break;
+        }
+    }
+    Ok(selected)
+}
+
+/// Absolute user source paths plus project-relative `src/...rs` paths that
+/// appear inside language strings.
+fn select_source_paths(result: &AnalysisResult) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut paths: BTreeSet<String> = result.user_source_paths.iter().cloned().collect();
+    let framework: Vec<String> = result.framework_source_paths.iter().map(|p| p.replace('\\', "/")).collect();
+    let relative = Regex::new(r"src[\\/](?:[A-Za-z0-9_\-]+[\\/])*[A-Za-z0-9_\-]+\.rs")?;
+    for s in &result.language_strings {
+        if is_common(&s.string) {
+            continue;
+        }
+        for mat in relative.find_iter(&s.string) {
+            // Preceded by a separator or version digit: the tail of a longer path.
+            let part_of_path = s.string[..mat.start()]
+                .chars()
+                .last()
+                .is_some_and(|c| matches!(c, '\\' | '/' | '.' | '-' | '_') || c.is_ascii_digit());
+            let normalized = mat.as_str().replace('\\', "/");
+            let in_dependency = framework.iter().any(|p| p.ends_with(&normalized));
+            if !part_of_path && !in_dependency {
+                paths.insert(mat.as_str().to_string());
+            }
+        }
+    }
+    Ok(paths.into_iter().take(MAX_SOURCE_PATHS).collect())
+}
+
+/// `C:\Users\<name>\`, `/home/<name>/` and `/Users/<name>/` prefixes of the build host.
+fn developer_homes(result: &AnalysisResult) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let home = Regex::new(r"(?:[A-Za-z]:\\Users\\|/home/|/Users/)([^\\/\s]+)[\\/]")?;
+    let texts = result
+        .framework_source_paths
+        .iter()
+        .chain(result.user_source_paths.iter())
+        .chain(result.language_strings.iter().map(|s| &s.string));
+    let mut homes = BTreeSet::new();
+    for text in texts {
+        for caps in home.captures_iter(text) {
+            let (Some(whole), Some(user)) = (caps.get(0), caps.get(1)) else { continue };
+            if !GENERIC_USERS.contains(&user.as_str().to_ascii_lowercase().as_str()) {
+                homes.insert(whole.as_str().to_string());
+            }
+        }
+    }
+    Ok(homes.into_iter().collect())
+}
+
+/// Crates with the most compiled-in source files, skipping std's own.
+fn top_crates(result: &AnalysisResult) -> Vec<String> {
+    let mut crates: Vec<(usize, &str)> = result
+        .crate_groups
+        .groups
+        .iter()
+        .filter(|g| !STD_DEPENDENCY_CRATES.contains(&g.name.as_str()))
+        .map(|g| (g.versions.iter().map(|v| v.source_files).sum(), g.name.as_str()))
+        .collect();
+    crates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+    crates.into_iter().take(MAX_META_CRATES).map(|(_, name)| name.to_string()).collect()
+}
+
+fn rule_name(result: &AnalysisResult) -> String {
+    let id: String = result.file.sha256.chars().take(12).collect();
+    if id.is_empty() {
+        "rustre_rust_binary".to_string()
+    } else {
+        format!("rustre_{}", id)
+    }
+}
+
+/// Build a YARA rule from the sample's distinctive indicators.
+pub fn generate_yara(result: &AnalysisResult) -> Result<String, Box<dyn std::error::Error>> {
+    let mut strings: Vec<String> = Vec::new();
+    strings.extend(select_source_paths(result)?);
+    strings.extend(developer_homes(result)?);
+    for s in select_language_strings(result)? {
+        if !strings.contains(&s) {
+            strings.push(s);
+        }
+    }
+    if strings.is_empty() {
+        return Err("no distinctive strings found for a YARA rule".into());
+    }
+
+    let mut rust_markers = vec![
+        "library/std/src".to_string(),
+        "library\\std\\src".to_string(),
+        ".cargo/registry".to_string(),
+        ".cargo\\registry".to_string(),
+    ];
+    if let Some(hash) = &result.rustc_hash {
+        rust_markers.insert(0, format!("/rustc/{}", hash));
+    }
+
+    let mut rule = String::new();
+    writeln!(rule, "rule {}", rule_name(result))?;
+    writeln!(rule, "{{")?;
+    writeln!(rule, "    meta:")?;
+    writeln!(rule, "        description = \"Rust binary indicators generated by rustre\"")?;
+    if !result.file.sha256.is_empty() {
+        writeln!(rule, "        sha256 = \"{}\"", result.file.sha256)?;
+    }
+    if let Some(version) = &result.rust_version {
+        writeln!(rule, "        rust_version = \"{}\"", escape(version))?;
+    }
+    if let Some(hash) = &result.rustc_hash {
+        writeln!(rule, "        rustc_hash = \"{}\"", escape(hash))?;
+    }
+    let crates = top_crates(result);
+    if !crates.is_empty() {
+        writeln!(rule, "        crates = \"{}\"", escape(&crates.join(", ")))?;
+    }
+    writeln!(rule)?;
+    writeln!(rule, "    strings:")?;
+    for (i, marker) in rust_markers.iter().enumerate() {
+        writeln!(rule, "        $rust{} = \"{}\" ascii", i, escape(marker))?;
+    }
+    for (i, s) in strings.iter().enumerate() {
+        writeln!(rule, "        $s{} = \"{}\" ascii", i, escape(s))?;
+    }
+    writeln!(rule)?;
+
+    // Tolerate a few indicators changing between builds of the same family.
+    let required = if strings.len() <= 3 { strings.len() } else { (strings.len() / 2).max(3) };
+    writeln!(rule, "    condition:")?;
+    writeln!(
+        rule,
+        "        {} and any of ($rust*) and {} of ($s*)",
+        EXECUTABLE_MAGIC,
+        required
+    )?;
+    writeln!(rule, "}}")?;
+    Ok(rule)
+}
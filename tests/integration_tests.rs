@@ -1,35 +1,73 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
 use tempfile::TempDir;
 use zip::ZipArchive;
-use rustre::{analyze_binary, load_version_mappings, Package, DEFAULT_MIN_STR_LEN};
+use rustre::{analyze_binary, load_or_analyze, load_version_mappings, AnalysisResult, Package, DEFAULT_MIN_STR_LEN};
+use rustre::baseline::{build_baseline, mark_known_strings, StringBaseline};
+use rustre::cluster::{cluster_directory, cluster_results, ClusterKind};
+use rustre::cyclonedx::to_cyclonedx;
+use rustre::diff::diff_results;
+use rustre::fingerprint::{crate_similarity, jaccard, versioned_similarity};
+use rustre::ownership::attribute_owners;
+use rustre::rules::{evaluate_rules, load_default_rules, parse_rules};
+use rustre::scaffold::scaffold_binary;
+use rustre::spdx::{to_spdx, to_tag_value};
+use rustre::update::{import_channel_dir, parse_channel_manifest};
+use rustre::verify::verify;
+use rustre::version_estimate::estimate_rust_version;
+use rustre::versions::{llvm_major, HashLookup, ToolchainInfo, VersionDatabase};
+use rustre::yara::generate_yara;
 
-/// Helper function to extract a password-protected zip file and return the path to the extracted binary
-fn extract_sample(zip_name: &str) -> (TempDir, PathBuf) {
+/// Helper function to extract a password-protected zip file into `dir` and return the path to the extracted binary
+fn extract_sample(zip_name: &str, dir: &Path) -> PathBuf {
     let zip_path = format!("tests/samples/{}", zip_name);
-    let temp_dir = TempDir::new().expect("Failed to create temp directory");
     
     // Extract using pure Rust zip crate with password "infected"
-    let file = File::open(&zip_path).expect(&format!("Failed to open zip file: {}", zip_path));
+    let file = File::open(&zip_path).unwrap_or_else(|_| panic!("Failed to open zip file: {}", zip_path));
     let mut archive = ZipArchive::new(file).expect("Failed to read zip archive");
     
     // Get the first file in the archive
     let mut zip_file = archive.by_index_decrypt(0, b"infected")
         .expect("Failed to decrypt file with password 'infected'");
     
-    let outpath = temp_dir.path().join(zip_file.name());
+    let outpath = dir.join(zip_file.name());
     
     // Security check to prevent directory traversal
-    if !outpath.starts_with(temp_dir.path()) {
+    if !outpath.starts_with(dir) {
         panic!("Invalid zip file path detected");
     }
     
     let mut outfile = File::create(&outpath).expect("Failed to create output file");
     std::io::copy(&mut zip_file, &mut outfile).expect("Failed to extract file");
     
-    (temp_dir, outpath)
+    outpath
+}
+
+type Cache<T> = OnceLock<Mutex<HashMap<String, Arc<OnceLock<T>>>>>;
+
+/// Compute a value once per test run, even when tests ask for it concurrently.
+fn cached<T: Clone>(cache: &Cache<T>, key: &str, init: impl FnOnce() -> T) -> T {
+    let cell = cache.get_or_init(Default::default).lock().unwrap().entry(key.to_string()).or_default().clone();
+    cell.get_or_init(init).clone()
+}
+
+/// Path of a sample, extracted once per test run into Cargo's temporary directory for tests.
+fn sample_path(filename: &str) -> PathBuf {
+    static PATHS: Cache<PathBuf> = OnceLock::new();
+    cached(&PATHS, filename, || {
+        let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("samples");
+        std::fs::create_dir_all(&dir).expect("Failed to create sample directory");
+        extract_sample(&format!("{}.zip", filename), &dir)
+    })
+}
+
+/// Analysis of a sample, computed once per test run.
+fn analyze_sample(filename: &str) -> AnalysisResult {
+    static RESULTS: Cache<AnalysisResult> = OnceLock::new();
+    cached(&RESULTS, filename, || analyze_binary(sample_path(filename).to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap())
 }
 
 #[test]
@@ -37,8 +75,7 @@ fn test_sample_005f7884f04fd8be032c875a714a6413933d6cebcda2b4fb06de2f88a42bb089_
     let filename = "005f7884f04fd8be032c875a714a6413933d6cebcda2b4fb06de2f88a42bb089.elf";
     
     // Extract from password-protected zip
-    let (_temp_dir, file_path) = extract_sample(&format!("{}.zip", filename));
-    let result = analyze_binary(file_path.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let result = analyze_sample(filename);
     
     let expected_packages = vec![
         Package { path: "/cargo/registry/src/index.crates.io-6f17d22bba15001f/rustc-demangle-0.1.21".to_string(), name: "rustc-demangle".to_string(), version: "0.1.21".to_string() },
@@ -65,8 +102,7 @@ fn test_sample_42b0897474819a5d21de10488fdc539eea10b96d6e0679d9836bd4c6b40875aa_
     let filename = "42b0897474819a5d21de10488fdc539eea10b96d6e0679d9836bd4c6b40875aa.elf";
     
     // Extract from password-protected zip
-    let (_temp_dir, file_path) = extract_sample(&format!("{}.zip", filename));
-    let result = analyze_binary(file_path.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let result = analyze_sample(filename);
     
     let expected_packages = vec![
         Package { path: "/cargo/registry/src/index.crates.io-6f17d22bba15001f/addr2line-0.17.0".to_string(), name: "addr2line".to_string(), version: "0.17.0".to_string() },
@@ -90,8 +126,7 @@ fn test_sample_42b0897474819a5d21de10488fdc539eea10b96d6e0679d9836bd4c6b40875aa_
 fn test_sample_5255ea080acd85ad274c48d1c4254c285c24f5ea67787666005c9a47c62ceb70_elf() {
     let filename = "5255ea080acd85ad274c48d1c4254c285c24f5ea67787666005c9a47c62ceb70.elf";
     
-    let (_temp_dir, file_path) = extract_sample(&format!("{}.zip", filename));
-    let result = analyze_binary(file_path.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let result = analyze_sample(filename);
     
     let expected_packages = vec![
         Package { path: "/cargo/registry/src/index.crates.io-6f17d22bba15001f/addr2line-0.17.0".to_string(), name: "addr2line".to_string(), version: "0.17.0".to_string() },
@@ -116,8 +151,7 @@ fn test_sample_c18b24be70e5227a6b383c94034210b85c809fba8eca7a06b8b2136d510efee5_
     let filename = "c18b24be70e5227a6b383c94034210b85c809fba8eca7a06b8b2136d510efee5.elf";
     
     // Extract from password-protected zip
-    let (_temp_dir, file_path) = extract_sample(&format!("{}.zip", filename));
-    let result = analyze_binary(file_path.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let result = analyze_sample(filename);
     
     assert!(result.user_source_paths.is_empty());
     assert_eq!(result.rustc_hash, Some("84c898d65adf2f39a5a98507f1fe0ce10a2b8dbc".to_string()));
@@ -133,8 +167,7 @@ fn test_sample_c18b24be70e5227a6b383c94034210b85c809fba8eca7a06b8b2136d510efee5_
 fn test_sample_855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808_exe() {
     let filename = "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe";
     
-    let (_temp_dir, file_path) = extract_sample(&format!("{}.zip", filename));
-    let result = analyze_binary(file_path.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let result = analyze_sample(filename);
     
     let expected_packages = vec![
         Package { path: ".cargo\\registry\\src\\github.com-1ecc6299db9ec823\\winsafe-0.0.12".to_string(), name: "winsafe".to_string(), version: "0.0.12".to_string() },
@@ -161,8 +194,7 @@ fn test_sample_855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808_
 fn test_sample_acc31048e00d1a0f4cd5569d5d4db539da8f506cc7a6a171942d015ecc817d43_exe() {
     let filename = "acc31048e00d1a0f4cd5569d5d4db539da8f506cc7a6a171942d015ecc817d43.exe";
     
-    let (_temp_dir, file_path) = extract_sample(&format!("{}.zip", filename));
-    let result = analyze_binary(file_path.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let result = analyze_sample(filename);
     
     let expected_packages = vec![
         Package { path: ".cargo\\registry\\src\\github.com-1ecc6299db9ec823\\winsafe-0.0.12".to_string(), name: "winsafe".to_string(), version: "0.0.12".to_string() },
@@ -187,8 +219,7 @@ fn test_sample_acc31048e00d1a0f4cd5569d5d4db539da8f506cc7a6a171942d015ecc817d43_
 fn test_sample_6d337b95ca3361f5fc5733591095765beab6917555777f078eafea3064f735bd_exe() {
     let filename = "6d337b95ca3361f5fc5733591095765beab6917555777f078eafea3064f735bd.exe";
     
-    let (_temp_dir, file_path) = extract_sample(&format!("{}.zip", filename));
-    let result = analyze_binary(file_path.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let result = analyze_sample(filename);
     
     let expected_user_paths = vec![
         "/src/peparser/pe.rs".to_string(),
@@ -219,8 +250,7 @@ fn test_sample_6d337b95ca3361f5fc5733591095765beab6917555777f078eafea3064f735bd_
 fn test_sample_8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1_exe() {
     let filename = "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe";
     
-    let (_temp_dir, file_path) = extract_sample(&format!("{}.zip", filename));
-    let result = analyze_binary(file_path.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let result = analyze_sample(filename);
     
     assert!(result.user_source_paths.is_empty());
     assert!(result.rustc_hash.is_some());
@@ -233,8 +263,7 @@ fn test_sample_8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1_
 fn test_sample_8765ef2a4575e52195223ecb045be569c08337e1ff73a894214b0644f7b480ba_exe() {
     let filename = "8765ef2a4575e52195223ecb045be569c08337e1ff73a894214b0644f7b480ba.exe";
     
-    let (_temp_dir, file_path) = extract_sample(&format!("{}.zip", filename));
-    let result = analyze_binary(file_path.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let result = analyze_sample(filename);
     
     let expected_user_paths = vec![
         "/src/ui.rs".to_string(),
@@ -267,6 +296,87 @@ fn test_version_mappings_functionality() {
     assert_eq!(mappings.get("4b91a6ea7258a947e59c6522cd5898e7c0a6a88f"), Some(&"1.63.0".to_string()));
 }
 
+#[test]
+fn test_version_database_schema() {
+    // The original flat file is migrated on load.
+    let flat = r#"{"aedd173a2c086e558c2b66d3743b344f977621a7": "1.77.0", "eb26296b556cef10fb713a38f3d16b9886080f26": "1.0.0-alpha"}"#;
+    let database = VersionDatabase::parse(flat).unwrap();
+    let info = database.get("aedd173a2c086e558c2b66d3743b344f977621a7").unwrap();
+    assert_eq!(info.channel, "stable");
+    assert_eq!(info.date.as_deref(), Some("2024-03-21"));
+    assert_eq!(info.llvm_major, Some(17));
+    assert_eq!(info.tag.as_deref(), Some("1.77.0"));
+    assert_eq!(info.describe(), "rustc 1.77.0 (stable) released 2024-03-21 bundling LLVM 17");
+    assert_eq!(database.get("eb26296b556cef10fb713a38f3d16b9886080f26").unwrap().channel, "pre-release");
+    assert_eq!(llvm_major("1.90.0"), Some(20));
+    assert_eq!(llvm_major("1.91.0-beta.3"), Some(21));
+    assert_eq!(llvm_major("1.95.0"), Some(22));
+    // Releases newer than the table are unknown rather than guessed.
+    assert_eq!(llvm_major("1.120.0"), None);
+
+    // Round trip through the versioned schema; a stable release wins over a nightly of the same commit.
+    let mut database = VersionDatabase::parse(&serde_json::to_string(&database).unwrap()).unwrap();
+    let nightly = ToolchainInfo {
+        version: "nightly-2024-02-01".to_string(),
+        channel: "nightly".to_string(),
+        date: Some("2024-01-31".to_string()),
+        llvm_major: Some(17),
+        tag: None,
+    };
+    database.insert("aedd173a2c086e558c2b66d3743b344f977621a7".to_string(), nightly);
+    assert_eq!(database.mappings()["aedd173a2c086e558c2b66d3743b344f977621a7"], "1.77.0");
+    assert!(VersionDatabase::parse(r#"{"schema_version": 99, "toolchains": {}}"#).is_err());
+
+    let filename = "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe";
+    let result = analyze_sample(filename);
+    let toolchain = result.toolchain.unwrap();
+    assert_eq!(toolchain.version, "1.63.0");
+    assert_eq!(toolchain.date.as_deref(), Some("2022-08-11"));
+    assert_eq!(toolchain.llvm_major, Some(14));
+}
+
+#[test]
+fn test_abbreviated_rustc_hash() {
+    let mut database = VersionDatabase::default();
+    for (hash, tag) in [
+        ("82e1608dfa6e0b5569232559e3d385fea5a93112", "1.75.0"),
+        ("82e1608d0000000000000000000000000000beef", "1.75.1"),
+        ("84c898d65adf2f39a5a98507f1fe0ce10a2b8dbc", "1.69.0"),
+    ] {
+        database.insert(hash.to_string(), ToolchainInfo::from_tag(tag));
+    }
+    match database.lookup("84C898D65") {
+        HashLookup::Found(full, info) => {
+            assert_eq!(full, "84c898d65adf2f39a5a98507f1fe0ce10a2b8dbc");
+            assert_eq!(info.version, "1.69.0");
+        }
+        other => panic!("expected a unique match, got {:?}", other),
+    }
+    assert_eq!(
+        database.lookup("82e1608d"),
+        HashLookup::Ambiguous(vec!["82e1608d0000000000000000000000000000beef", "82e1608dfa6e0b5569232559e3d385fea5a93112"])
+    );
+    // Too short to be a meaningful prefix.
+    assert_eq!(database.lookup("84c898"), HashLookup::NotFound);
+    assert_eq!(database.lookup("0123456789"), HashLookup::NotFound);
+
+    // A `.comment`-style version string resolves against rust_versions.json.
+    let temp_dir = TempDir::new().unwrap();
+    let binary = temp_dir.path().join("comment.bin");
+    std::fs::write(&binary, b"\x7fELF\0\0GCC: (GNU) 13.2.0\0rustc version 1.69.0 (84c898d65 2023-04-16)\0").unwrap();
+    let result = analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    assert_eq!(result.rustc_hash.as_deref(), Some("84c898d65adf2f39a5a98507f1fe0ce10a2b8dbc"));
+    assert_eq!(result.rust_version.as_deref(), Some("1.69.0"));
+    assert!(result.rustc_hash_candidates.is_empty());
+
+    // Unknown commits still report the embedded version.
+    std::fs::write(&binary, b"rustc 1.99.0-nightly (0123456789 2031-01-01)\0").unwrap();
+    let result = analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    assert_eq!(result.rustc_hash.as_deref(), Some("0123456789"));
+    assert_eq!(result.rust_version.as_deref(), Some("1.99.0-nightly"));
+    assert!(result.toolchain.is_none());
+}
+
 #[test]
 fn test_analyze_binary_error_handling() {
     // Test with non-existent file
@@ -293,4 +403,1098 @@ fn test_package_uniqueness() {
     set.insert(pkg2.clone());
     
     assert_eq!(set.len(), 2, "Packages with different paths should be treated as different");
-} 
\ No newline at end of file
+} 
+
+#[test]
+fn test_cyclonedx_output() {
+    let filename = "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe";
+
+    let result = analyze_sample(filename);
+    let bom = serde_json::to_value(to_cyclonedx(&result)).unwrap();
+
+    assert_eq!(bom["bomFormat"], "CycloneDX");
+    assert_eq!(bom["specVersion"], "1.5");
+    assert_eq!(bom["metadata"]["component"]["name"], filename);
+    assert_eq!(bom["metadata"]["component"]["hashes"][1]["alg"], "SHA-256");
+    assert_eq!(
+        bom["metadata"]["component"]["hashes"][1]["content"],
+        "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808"
+    );
+
+    let tools = bom["metadata"]["tools"]["components"].as_array().unwrap();
+    assert!(tools.iter().any(|t| t["name"] == "rustc" && t["version"] == "1.63.0"));
+
+    let components = bom["components"].as_array().unwrap();
+    let demangle = components
+        .iter()
+        .find(|c| c["purl"] == "pkg:cargo/rustc-demangle@0.1.21")
+        .expect("rustc-demangle component missing");
+    let occurrences = demangle["evidence"]["occurrences"].as_array().unwrap();
+    assert!(occurrences.iter().any(|o| o["location"].as_str().unwrap().ends_with("rustc-demangle-0.1.21\\src\\v0.rs")));
+
+    let depends_on = bom["dependencies"][0]["dependsOn"].as_array().unwrap();
+    assert_eq!(depends_on.len(), components.len());
+    assert_eq!(bom["serialNumber"], "urn:uuid:855f411b-d066-5b65-8c4f-2fd3c9fbb4fa");
+
+    // Reports saved before file hashes were recorded still get a serial number.
+    let mut old_report = result.clone();
+    old_report.file.sha256 = String::new();
+    let serial = to_cyclonedx(&old_report).serial_number;
+    assert!(serial.starts_with("urn:uuid:"));
+    assert_eq!(serial, to_cyclonedx(&old_report).serial_number);
+}
+
+#[test]
+fn test_spdx_output() {
+    let filename = "005f7884f04fd8be032c875a714a6413933d6cebcda2b4fb06de2f88a42bb089.elf";
+
+    let result = analyze_sample(filename);
+    let doc = to_spdx(&result);
+    let json = serde_json::to_value(&doc).unwrap();
+
+    assert_eq!(json["spdxVersion"], "SPDX-2.3");
+    assert_eq!(json["packages"][0]["SPDXID"], "SPDXRef-Binary-005f7884f04fd8be");
+    assert_eq!(json["packages"][0]["checksums"][1]["checksumValue"], "005f7884f04fd8be032c875a714a6413933d6cebcda2b4fb06de2f88a42bb089");
+
+    let gimli = json["packages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["name"] == "gimli")
+        .expect("gimli package missing");
+    assert_eq!(gimli["externalRefs"][0]["referenceLocator"], "pkg:cargo/gimli@0.26.2");
+
+    let contains = json["relationships"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|r| r["relationshipType"] == "CONTAINS" && r["spdxElementId"] == "SPDXRef-Binary-005f7884f04fd8be")
+        .count();
+    assert_eq!(contains, json["packages"].as_array().unwrap().len() - 1);
+    assert!(json["annotations"][0]["comment"].as_str().unwrap().contains("heuristically"));
+
+    let tag_value = to_tag_value(&doc).unwrap();
+    assert!(tag_value.starts_with("SPDXVersion: SPDX-2.3\n"));
+    assert!(tag_value.contains("Relationship: SPDXRef-Binary-005f7884f04fd8be CONTAINS SPDXRef-Crate-gimli-0.26.2"));
+    assert!(tag_value.contains("ExternalRef: PACKAGE-MANAGER purl pkg:cargo/gimli@0.26.2"));
+
+    // Every document about the binary gets its own namespace.
+    assert_ne!(doc.document_namespace, to_spdx(&result).document_namespace);
+}
+
+#[test]
+fn test_verify_against_cargo_lock() {
+    let filename = "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe";
+
+    let result = analyze_sample(filename);
+
+    let temp_dir = TempDir::new().unwrap();
+    let lock_path = temp_dir.path().join("Cargo.lock");
+    std::fs::write(&lock_path, r#"
+version = 3
+
+[[package]]
+name = "sample"
+version = "0.1.0"
+
+[[package]]
+name = "base64"
+version = "0.13.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "cipher"
+version = "0.4.4"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "rand_core"
+version = "0.5.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "serde"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#).unwrap();
+
+    let report = verify(&result, lock_path.to_str().unwrap()).unwrap();
+
+    assert!(!report.passed);
+    assert!(report.undeclared.iter().any(|c| c.name == "winsafe"), "winsafe should be undeclared");
+    assert!(!report.undeclared.iter().any(|c| c.name == "base64" || c.name == "base"));
+    assert_eq!(report.version_mismatches.len(), 1);
+    assert_eq!(report.version_mismatches[0].name, "cipher");
+    assert_eq!(report.version_mismatches[0].binary_versions, vec!["0.4.3".to_string()]);
+    assert_eq!(report.not_evidenced.len(), 1);
+    assert_eq!(report.not_evidenced[0].name, "serde");
+    assert!(report.toolchain_crates.iter().any(|c| c.name == "rustc-demangle"));
+}
+
+#[test]
+fn test_verify_against_own_sbom() {
+    let filename = "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe";
+
+    let result = analyze_sample(filename);
+
+    let temp_dir = TempDir::new().unwrap();
+    let bom_path = temp_dir.path().join("bom.cdx.json");
+    std::fs::write(&bom_path, serde_json::to_string(&to_cyclonedx(&result)).unwrap()).unwrap();
+    let report = verify(&result, bom_path.to_str().unwrap()).unwrap();
+    assert!(report.passed, "binary should match its own CycloneDX BOM: {:?}", report);
+    assert!(report.not_evidenced.is_empty());
+
+    let spdx_path = temp_dir.path().join("bom.spdx.json");
+    std::fs::write(&spdx_path, serde_json::to_string(&to_spdx(&result)).unwrap()).unwrap();
+    let report = verify(&result, spdx_path.to_str().unwrap()).unwrap();
+    assert!(report.passed, "binary should match its own SPDX document: {:?}", report);
+}
+
+#[test]
+fn test_scaffold_generation() {
+    let filename = "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe";
+
+    let file_path = sample_path(filename);
+    let result = analyze_sample(filename);
+
+    let temp_dir = TempDir::new().unwrap();
+    let out_dir = temp_dir.path().join("rebuild");
+    let written = scaffold_binary(file_path.to_str().unwrap(), &result, &out_dir).unwrap();
+    assert_eq!(written.len(), 4);
+
+    let manifest: toml::Value = std::fs::read_to_string(out_dir.join("Cargo.toml")).unwrap().parse().unwrap();
+    let dependencies = manifest["dependencies"].as_table().unwrap();
+    assert_eq!(dependencies["winsafe"].as_str(), Some("=0.0.12"));
+    assert_eq!(dependencies["cipher"].as_str(), Some("=0.4.3"));
+    assert_eq!(dependencies["base64"].as_str(), Some("*"));
+    assert!(!dependencies.contains_key("rustc-demangle"), "std dependencies should be commented out");
+
+    let toolchain: toml::Value = std::fs::read_to_string(out_dir.join("rust-toolchain.toml")).unwrap().parse().unwrap();
+    assert_eq!(toolchain["toolchain"]["channel"].as_str(), Some("1.63.0"));
+
+    let config: toml::Value = std::fs::read_to_string(out_dir.join(".cargo").join("config.toml")).unwrap().parse().unwrap();
+    assert_eq!(config["build"]["target"].as_str(), Some("x86_64-pc-windows-msvc"));
+
+    // A second run must not clobber the existing project, nor any single file of it.
+    assert!(scaffold_binary(file_path.to_str().unwrap(), &result, &out_dir).is_err());
+    std::fs::remove_file(out_dir.join("Cargo.toml")).unwrap();
+    std::fs::write(out_dir.join("src").join("main.rs"), "fn main() { println!(\"mine\"); }\n").unwrap();
+    assert!(scaffold_binary(file_path.to_str().unwrap(), &result, &out_dir).is_err());
+    assert!(std::fs::read_to_string(out_dir.join("src").join("main.rs")).unwrap().contains("mine"));
+
+    // Nightly builds pin the nightly channel, dated when the commit date is known.
+    let channel = |result: &rustre::AnalysisResult, name: &str| {
+        let out_dir = temp_dir.path().join(name);
+        scaffold_binary(file_path.to_str().unwrap(), result, &out_dir).unwrap();
+        let toolchain: toml::Value = std::fs::read_to_string(out_dir.join("rust-toolchain.toml")).unwrap().parse().unwrap();
+        toolchain["toolchain"]["channel"].as_str().unwrap().to_string()
+    };
+    let mut nightly = result.clone();
+    nightly.rust_version = Some("1.80.0-nightly".to_string());
+    nightly.toolchain = None;
+    assert_eq!(channel(&nightly, "nightly"), "nightly");
+    nightly.toolchain = Some(ToolchainInfo {
+        version: "1.80.0-nightly".to_string(),
+        channel: "nightly".to_string(),
+        date: Some("2024-05-02".to_string()),
+        llvm_major: Some(18),
+        tag: None,
+    });
+    assert_eq!(channel(&nightly, "nightly_dated"), "nightly-2024-05-03");
+    nightly.rust_version = Some("nightly-2024-05-03".to_string());
+    assert_eq!(channel(&nightly, "nightly_manifest"), "nightly-2024-05-03");
+}
+
+#[test]
+fn test_duplicate_crate_report() {
+    let filename = "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe";
+
+    let result = analyze_sample(filename);
+    let report = &result.crate_groups;
+
+    assert_eq!(report.duplicates.names, vec!["widestring".to_string()]);
+    assert_eq!(report.duplicates.duplicated_crates, 1);
+    assert_eq!(report.duplicates.extra_versions, 1);
+
+    let widestring = report.groups.iter().find(|g| g.name == "widestring").unwrap();
+    let versions: Vec<Option<&str>> = widestring.versions.iter().map(|v| v.version.as_deref()).collect();
+    assert_eq!(versions, vec![Some("0.5.1"), Some("1.2.0")]);
+    assert!(widestring.versions.iter().all(|v| v.source_files > 0));
+
+    // Unversioned registry directories are reported under their full crate name.
+    let base64 = report.groups.iter().find(|g| g.name == "base64").unwrap();
+    assert_eq!(base64.versions[0].version, None);
+}
+
+#[test]
+fn test_package_evidence_and_confidence() {
+    let filename = "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe";
+
+    let mut result = analyze_sample(filename);
+    assert_eq!(result.package_evidence.len(), result.packages.len());
+
+    let h2 = result.package_evidence.iter().find(|e| e.name == "h2").unwrap();
+    assert!(h2.source_files > 20);
+    assert!(h2.panic_locations_seen);
+    assert_eq!(h2.offsets.len(), h2.source_files);
+
+    let stray = result.package_evidence.iter().find(|e| e.name == "bzip2-sys").unwrap();
+    assert_eq!(stray.source_files, 0);
+    assert_eq!(stray.offsets.len(), 1);
+    assert!(!stray.panic_locations_seen);
+    assert!(stray.confidence < h2.confidence);
+
+    result.retain_min_confidence(0.5);
+    assert!(result.packages.iter().any(|p| p.name == "h2"));
+    assert!(!result.packages.iter().any(|p| p.name == "bzip2-sys"));
+    assert!(result.package_evidence.iter().all(|e| e.confidence >= 0.5));
+    assert!(!result.crate_groups.groups.iter().any(|g| g.name == "bzip2-sys"));
+    let crates: Vec<String> = result.packages.iter().map(|p| format!("crate:{}", p.crate_name_and_version().0)).collect();
+    assert!(result.capabilities.iter().flat_map(|c| &c.evidence).all(|e| !e.starts_with("crate:") || crates.contains(e)));
+
+    // A source path with no panic `Location` pointing at it.
+    let temp_dir = TempDir::new().unwrap();
+    let binary = temp_dir.path().join("paths.bin");
+    let path = "/home/dev/.cargo/registry/src/index.crates.io-6f17d22bba15001f/anyhow-1.0.80/src/error.rs";
+    std::fs::write(&binary, minimal_elf(&[(".rodata", path.as_bytes())])).unwrap();
+    let result = analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let anyhow = result.package_evidence.iter().find(|e| e.name == "anyhow").unwrap();
+    assert_eq!(anyhow.source_files, 1);
+    assert!(!anyhow.panic_locations_seen);
+}
+
+#[test]
+fn test_package_evidence_symbols() {
+    let filename = "005f7884f04fd8be032c875a714a6413933d6cebcda2b4fb06de2f88a42bb089.elf";
+
+    let result = analyze_sample(filename);
+
+    let demangle = result.package_evidence.iter().find(|e| e.name == "rustc-demangle").unwrap();
+    assert!(demangle.symbols_seen, "unstripped ELF should carry rustc_demangle symbols");
+    assert!(demangle.confidence > 0.7);
+}
+
+#[test]
+fn test_capability_inference() {
+    let filename = "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe";
+
+    let result = analyze_sample(filename);
+
+    let capability = |name: &str| result.capabilities.iter().find(|c| c.name == name);
+
+    let networking = capability("networking").expect("networking capability missing");
+    assert!(networking.evidence.contains(&"crate:reqwest".to_string()));
+    assert_eq!(networking.attack, vec!["T1071".to_string()]);
+
+    assert!(capability("crypto").unwrap().evidence.contains(&"crate:aes-gcm".to_string()));
+    assert!(capability("registry").unwrap().evidence.contains(&"crate:winreg".to_string()));
+    assert_eq!(capability("screen_capture").unwrap().attack, vec!["T1113".to_string()]);
+    assert!(capability("process_injection").is_none());
+}
+
+#[test]
+fn test_capability_inference_from_std_paths() {
+    let filename = "005f7884f04fd8be032c875a714a6413933d6cebcda2b4fb06de2f88a42bb089.elf";
+
+    let result = analyze_sample(filename);
+
+    let process = result.capabilities.iter().find(|c| c.name == "process_execution").unwrap();
+    assert!(process.evidence.iter().all(|e| e.starts_with("std:std/src/")));
+    assert!(process.evidence.contains(&"std:std/src/sys_common/process.rs".to_string()));
+}
+
+#[test]
+fn test_std_module_map() {
+    let filename = "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe";
+
+    let result = analyze_sample(filename);
+
+    let module = |krate: &str, name: &str| {
+        result.std_modules.iter().find(|m| m.krate == krate && m.module == name)
+    };
+
+    let windows = module("std", "sys::pal::windows").expect("sys::pal::windows missing");
+    assert_eq!(windows.platform.as_deref(), Some("windows"));
+    assert!(windows.files.contains(&"sys::pal::windows::io".to_string()));
+
+    let mpmc = module("std", "sync::mpmc").expect("sync::mpmc missing");
+    assert!(mpmc.files.contains(&"sync::mpmc".to_string()));
+    assert!(mpmc.platform.is_none());
+
+    assert!(module("std", "thread").unwrap().platform.is_none());
+    assert!(module("alloc", "collections::btree").is_some());
+}
+
+#[test]
+fn test_std_module_map_elf() {
+    let filename = "005f7884f04fd8be032c875a714a6413933d6cebcda2b4fb06de2f88a42bb089.elf";
+
+    let result = analyze_sample(filename);
+
+    let unix = result.std_modules.iter().find(|m| m.krate == "std" && m.module == "sys::unix").unwrap();
+    assert_eq!(unix.platform.as_deref(), Some("unix"));
+    assert!(unix.files.contains(&"sys::unix::net".to_string()));
+    assert!(result.std_modules.iter().any(|m| m.krate == "std" && m.module == "env"));
+    // Backtrace sources reached through `std/src/../../` are not std modules.
+    assert!(!result.std_modules.iter().any(|m| m.files.iter().any(|f| f.contains(".."))));
+}
+
+#[test]
+fn test_crate_fingerprint() {
+    let first = analyze_sample("855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe");
+    let sibling = analyze_sample("acc31048e00d1a0f4cd5569d5d4db539da8f506cc7a6a171942d015ecc817d43.exe");
+    let other = analyze_sample("8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe");
+
+    let fingerprint = &first.crate_fingerprint;
+    assert_eq!(fingerprint.crates, vec!["base64", "cipher", "rand-core", "winsafe"]);
+    assert!(fingerprint.versioned_crates.contains(&"cipher@0.4.3".to_string()));
+    assert!(fingerprint.versioned_crates.contains(&"base64".to_string()));
+    // rustc-demangle is linked by std and says nothing about the program.
+    assert!(!fingerprint.crates.iter().any(|c| c == "rustc-demangle"));
+    assert_eq!(fingerprint.crate_hash.len(), 64);
+    assert_ne!(fingerprint.crate_hash, fingerprint.versioned_hash);
+
+    assert_eq!(fingerprint.crate_hash, sibling.crate_fingerprint.crate_hash);
+    assert_eq!(crate_similarity(fingerprint, &sibling.crate_fingerprint), 1.0);
+    assert_eq!(versioned_similarity(fingerprint, &sibling.crate_fingerprint), 1.0);
+    assert_ne!(fingerprint.crate_hash, other.crate_fingerprint.crate_hash);
+    assert!(crate_similarity(fingerprint, &other.crate_fingerprint) < 0.1);
+}
+
+#[test]
+fn test_jaccard_similarity() {
+    let set = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(jaccard(&set(&["a", "b", "c"]), &set(&["b", "c", "d"])), 0.5);
+    assert_eq!(jaccard(&set(&["a"]), &set(&["b"])), 0.0);
+    assert_eq!(jaccard(&set(&[]), &set(&[])), 1.0);
+}
+
+#[test]
+fn test_cluster_directory() {
+    let samples_dir = TempDir::new().unwrap();
+    for filename in [
+        "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe",
+        "acc31048e00d1a0f4cd5569d5d4db539da8f506cc7a6a171942d015ecc817d43.exe",
+        "005f7884f04fd8be032c875a714a6413933d6cebcda2b4fb06de2f88a42bb089.elf",
+        "42b0897474819a5d21de10488fdc539eea10b96d6e0679d9836bd4c6b40875aa.elf",
+    ] {
+        std::fs::copy(sample_path(filename), samples_dir.path().join(filename)).unwrap();
+    }
+    // A saved report clusters like the binary it came from.
+    let report = analyze_sample("8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe");
+    std::fs::write(samples_dir.path().join("8ac5.json"), serde_json::to_string(&report).unwrap()).unwrap();
+
+    let report = cluster_directory(samples_dir.path(), DEFAULT_MIN_STR_LEN, 0.7).unwrap();
+    assert_eq!(report.samples.len(), 5);
+    assert!(report.skipped.is_empty());
+    let saved = report.samples.iter().find(|s| s.path.ends_with("8ac5.json")).unwrap();
+    assert_eq!(saved.sha256, "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1");
+
+    let crate_set: Vec<_> = report.clusters.iter().filter(|c| c.kind == ClusterKind::CrateSet).collect();
+    assert_eq!(crate_set.len(), 1);
+    assert_eq!(crate_set[0].members.len(), 2);
+    assert!(crate_set[0].members.iter().all(|m| m.ends_with(".exe")));
+    assert!(crate_set[0].shared.contains(&"winsafe".to_string()));
+
+    let by_hash: Vec<_> = report.clusters.iter().filter(|c| c.kind == ClusterKind::RustcHash).collect();
+    assert_eq!(by_hash.len(), 2);
+    let elf = by_hash.iter().find(|c| c.members[0].ends_with(".elf")).unwrap();
+    assert!(elf.shared.contains(&"rust_version:1.69.0".to_string()));
+}
+
+#[test]
+fn test_cluster_by_user_paths() {
+    let base = analyze_sample("005f7884f04fd8be032c875a714a6413933d6cebcda2b4fb06de2f88a42bb089.elf");
+    let with_paths = |paths: &[&str]| {
+        let mut result = base.clone();
+        result.rustc_hash = None;
+        result.user_source_paths = paths.iter().map(|p| p.to_string()).collect();
+        result
+    };
+    let samples = vec![
+        ("a".to_string(), with_paths(&["/home/dev/stealer/src/main.rs", "/home/dev/stealer/src/grab.rs"])),
+        ("b".to_string(), with_paths(&["/home/dev/stealer/src/main.rs", "/home/dev/stealer/src/grab.rs"])),
+        ("c".to_string(), with_paths(&["C:\\Users\\bob\\tool\\src\\main.rs"])),
+    ];
+
+    let clusters = cluster_results(&samples, 0.7);
+    assert_eq!(clusters.len(), 1);
+    assert_eq!(clusters[0].kind, ClusterKind::UserPaths);
+    assert_eq!(clusters[0].members, vec!["a", "b"]);
+    assert_eq!(clusters[0].shared.len(), 2);
+    assert_eq!(clusters[0].min_similarity, 1.0);
+}
+
+#[test]
+fn test_diff_results() {
+    let old = analyze_sample("855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe");
+    let new = analyze_sample("8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe");
+
+    let diff = diff_results(&old, &new);
+    assert!(!diff.unchanged);
+    assert!(diff.crates.removed.contains(&"winsafe@0.0.12".to_string()));
+    assert!(diff.crates.added.iter().any(|c| c.starts_with("reqwest@")));
+    let cipher = diff.crates.changed.iter().find(|c| c.name == "cipher").unwrap();
+    assert_eq!(cipher.old_versions, vec![Some("0.4.3".to_string())]);
+    assert_eq!(cipher.new_versions, vec![Some("0.3.0".to_string())]);
+    // base64 appears unversioned in both and is not reported.
+    assert!(!diff.crates.changed.iter().any(|c| c.name == "base64"));
+
+    let toolchain = diff.toolchain.as_ref().unwrap();
+    assert_eq!(toolchain.old_rust_version.as_deref(), Some("1.63.0"));
+    assert_eq!(toolchain.new_rust_version.as_deref(), Some("1.85.0"));
+    assert!(!diff.language_strings.added.is_empty());
+    assert!(!diff.language_strings.removed.is_empty());
+}
+
+#[test]
+fn test_diff_against_saved_report() {
+    let result = analyze_sample("855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe");
+    let temp_dir = TempDir::new().unwrap();
+    let report_path = temp_dir.path().join("report.json");
+    std::fs::write(&report_path, serde_json::to_string(&result).unwrap()).unwrap();
+
+    let saved = load_or_analyze(report_path.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let diff = diff_results(&saved, &result);
+    assert!(diff.unchanged);
+    assert!(diff.toolchain.is_none());
+    assert_eq!(diff.old.sha256, diff.new.sha256);
+
+    // Reports saved before evidence scoring keep their packages under --min-confidence.
+    let mut old_report = serde_json::to_value(&result).unwrap();
+    old_report.as_object_mut().unwrap().remove("package_evidence");
+    std::fs::write(&report_path, old_report.to_string()).unwrap();
+    let mut saved = load_or_analyze(report_path.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    saved.retain_min_confidence(0.9);
+    assert_eq!(saved.packages.len(), result.packages.len());
+}
+
+#[test]
+fn test_yara_rule_generation() {
+    let filename = "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe";
+    let result = analyze_sample(filename);
+
+    let rule = generate_yara(&result).unwrap();
+    assert!(rule.starts_with("rule rustre_855f411bd066\n{"));
+    assert!(rule.contains(&format!("sha256 = \"{}\"", filename.trim_end_matches(".exe"))));
+    assert!(rule.contains("rust_version = \"1.63.0\""));
+    assert!(rule.contains("crates = \"cipher, rand_core, winsafe, base64\""));
+    assert!(rule.contains("$rust0 = \"/rustc/4b91a6ea7258a947e59c6522cd5898e7c0a6a88f\" ascii"));
+    // Project paths, the developer's home and payload strings, escaped for YARA.
+    assert!(rule.contains("\"src\\\\windowsapi.rs\""));
+    assert!(rule.contains("\"C:\\\\Users\\\\user\\\\\""));
+    assert!(rule.contains("Couldn't delete shadow copies from volume!"));
+    // CI runner homes, std messages and registry paths are left out.
+    assert!(!rule.contains("runneradmin"));
+    assert!(!rule.contains("panicked"));
+    assert!(!rule.contains("rustc-demangle"));
+    assert!(rule.contains("and any of ($rust*) and "));
+    assert!(rule.contains("uint32(0) == 0xFEEDFACE") && rule.contains("uint32be(0) == 0xCAFEBABE"));
+    assert!(rule.trim_end().ends_with('}'));
+
+    // Payload strings sharing words with API names or format specifiers are kept.
+    let mut payload = result.clone();
+    let mut string = payload.language_strings[0].clone();
+    string.string = "LoadLibrary(%q) failed: permission denied".to_string();
+    string.known_library = false;
+    payload.language_strings = vec![string];
+    assert!(generate_yara(&payload).unwrap().contains("LoadLibrary(%q) failed: permission denied"));
+}
+
+#[test]
+fn test_yara_rule_requires_distinctive_strings() {
+    let filename = "005f7884f04fd8be032c875a714a6413933d6cebcda2b4fb06de2f88a42bb089.elf";
+    let result = analyze_sample(filename);
+
+    assert!(generate_yara(&result).is_err());
+}
+
+#[test]
+fn test_known_library_strings() {
+    let filename = "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe";
+    let mut result = analyze_sample(filename);
+
+    let find = |result: &rustre::AnalysisResult, s: &str| result.language_strings.iter().find(|e| e.string == s).cloned();
+    assert!(find(&result, "called `Result::unwrap()` on an `Err` value").unwrap().known_library);
+    assert!(find(&result, "`scalar deleting destructor'").unwrap().known_library);
+    let payload = "Couldn't delete shadow copies from volume! GetLastError: ";
+    assert!(!find(&result, payload).unwrap().known_library);
+
+    let total = result.language_strings.len();
+    result.retain_unique_strings();
+    assert!(result.language_strings.len() < total);
+    assert!(result.language_strings.iter().all(|s| !s.known_library));
+    assert!(find(&result, payload).is_some());
+
+    // Baseline strings of crates the binary does not link are not matched.
+    let mut baseline = StringBaseline::default();
+    baseline.insert("tokio", "*", payload);
+    mark_known_strings(&mut result.language_strings, &baseline, &result.packages);
+    assert!(!find(&result, payload).unwrap().known_library);
+    let mut packages = result.packages.clone();
+    packages.push(Package { path: "tokio-1.44.2".to_string(), name: "tokio".to_string(), version: "1.44.2".to_string() });
+    mark_known_strings(&mut result.language_strings, &baseline, &packages);
+    assert!(find(&result, payload).unwrap().known_library);
+}
+
+#[test]
+fn test_string_owners() {
+    let filename = "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe";
+    let result = analyze_sample(filename);
+    let find = |s: &str| result.language_strings.iter().find(|e| e.string == s).unwrap();
+
+    let wmi = find("root\\SecurityCenter2SELECT displayName FROM AntiVirusProduct");
+    assert_eq!(wmi.source_path.as_deref(), Some("src\\main.rs"));
+    assert_eq!(wmi.owner.as_deref(), Some("<user>"));
+    assert_eq!(wmi.owner_evidence.as_deref(), Some("location"));
+
+    let jpeg = find("use of unset quantization table");
+    assert_eq!(jpeg.owner.as_deref(), Some("jpeg-decoder@0.3.1"));
+
+    let tokio = find("JoinHandle polled after completion");
+    assert_eq!(tokio.owner.as_deref(), Some("tokio@1.44.2"));
+    assert_eq!(tokio.owner_evidence.as_deref(), Some("baseline"));
+
+    // Baseline strings of crates the binary does not link are not attributed.
+    let mut strings = vec![wmi.clone()];
+    strings[0].source_path = None;
+    strings[0].owner = None;
+    strings[0].owner_evidence = None;
+    let mut baseline = StringBaseline::default();
+    baseline.insert("actix-web", "*", &wmi.string);
+    attribute_owners(&mut strings, &result.packages, Some(&baseline));
+    assert_eq!(strings[0].owner, None);
+    baseline.insert("tokio", "*", &wmi.string);
+    attribute_owners(&mut strings, &result.packages, Some(&baseline));
+    assert_eq!(strings[0].owner.as_deref(), Some("tokio@1.44.2"));
+    assert_eq!(strings[0].owner_evidence.as_deref(), Some("baseline"));
+}
+
+#[test]
+fn test_build_string_baseline() {
+    let reference_dir = TempDir::new().unwrap();
+    for filename in [
+        "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe",
+        "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe",
+    ] {
+        std::fs::copy(sample_path(filename), reference_dir.path().join(filename)).unwrap();
+    }
+
+    let baseline = build_baseline(reference_dir.path(), DEFAULT_MIN_STR_LEN).unwrap();
+    // Only the first binary links winsafe, so its own strings go to winsafe.
+    let winsafe = &baseline.crates["winsafe"]["0.0.12"];
+    assert!(winsafe.contains("Couldn't delete shadow copies from volume! GetLastError: "));
+    // Strings in both binaries could be std's or any crate both link.
+    assert!(!baseline.crates.values().flat_map(|v| v.values()).any(|s| s.contains("capacity overflow")));
+}
+
+#[test]
+fn test_family_rules() {
+    let filename = "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe";
+    let mut result = analyze_sample(filename);
+    // Rules are only evaluated when asked for.
+    assert!(result.rule_matches.is_empty());
+    // rules.toml in the repository root is the default rule set.
+    let matches = evaluate_rules(&load_default_rules().unwrap(), &result);
+    assert!(matches.iter().any(|m| m.name == "nevada_ransomware"));
+
+    let rules = parse_rules(
+        r#"
+[[rule]]
+name = "cipher_04"
+tags = ["crypto"]
+packages = [{ name = "cipher", version = ">=0.4, <0.5" }, { name = "rand-core" }]
+rust_version = "~1.63"
+rustc_hash = "4b91a6ea"
+
+[[rule]]
+name = "cipher_05"
+packages = [{ name = "cipher", version = ">=0.5" }]
+
+[[rule]]
+name = "project_layout"
+user_source_paths = ["**/src/bin/*.rs", "C:/Users/*/nevada/src/main.rs"]
+language_strings = ["^nevadasch_scmanager"]
+"#,
+    )
+    .unwrap();
+
+    let matches = evaluate_rules(&rules, &result);
+    let names: Vec<&str> = matches.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, vec!["cipher_04"]);
+    assert_eq!(matches[0].tags, vec!["crypto"]);
+    assert!(matches[0].matched.contains(&"package:cipher@0.4.3".to_string()));
+    assert!(matches[0].matched.contains(&"rust_version:1.63.0".to_string()));
+
+    result.user_source_paths.insert("C:\\Users\\dev\\nevada\\src\\main.rs".to_string());
+    result.user_source_paths.insert("C:\\Users\\dev\\nevada\\src\\bin\\tool.rs".to_string());
+    let matches = evaluate_rules(&rules, &result);
+    assert!(matches.iter().any(|m| m.name == "project_layout"));
+
+    // Nightly and beta toolchains match as the release they became.
+    let rules = parse_rules("[[rule]]\nname = \"rust_180\"\nrust_version = \"~1.80\"\n").unwrap();
+    for (version, matched) in [
+        ("1.80.0-nightly", true),
+        ("1.80.0-beta.4", true),
+        ("nightly-2024-05-03", true),
+        ("beta-2024-05-03", false),
+        ("1.80.1", true),
+        ("nightly", false),
+    ] {
+        result.rust_version = Some(version.to_string());
+        assert_eq!(!evaluate_rules(&rules, &result).is_empty(), matched, "{}", version);
+    }
+}
+
+#[test]
+fn test_invalid_rules() {
+    assert!(parse_rules("[[rule]]\nname = \"empty\"\n").is_err());
+    assert!(parse_rules("[[rule]]\nname = \"bad\"\nlanguage_strings = [\"(\"]\n").is_err());
+    assert!(parse_rules("[[rule]]\nname = \"bad\"\npackages = [{ name = \"aes\", version = \"not a range\" }]\n").is_err());
+    assert!(parse_rules("[[rule]]\nname = \"typo\"\npackage = []\n").is_err());
+    assert!(parse_rules("").unwrap().is_empty());
+}
+
+fn channel_manifest(date: &str, version: &str, hash: &str) -> String {
+    format!(
+        "manifest-version = \"2\"\ndate = \"{}\"\n\n[pkg.rustc]\nversion = \"{}\"\ngit_commit_hash = \"{}\"\n\n[pkg.rustc.target.x86_64-pc-windows-msvc]\navailable = true\n",
+        date, version, hash
+    )
+}
+
+#[test]
+fn test_channel_manifest_import() {
+    let nightly_hash = "9c9b568792ef5ce82a6b7ed6e2b6d4e8c5a6d2d4";
+    let beta_hash = "a269819ac1e1a5f6c4b5c1a0b5b8f8a5f6d7e8c9";
+
+    let (hash, build) =
+        parse_channel_manifest(&channel_manifest("2024-05-03", "1.79.0-beta.4 (a269819ac 2024-05-01)", beta_hash)).unwrap();
+    assert_eq!(hash, beta_hash);
+    assert_eq!(build.version, "1.79.0-beta.4");
+    assert_eq!(build.channel, "beta");
+    assert_eq!(build.date.as_deref(), Some("2024-05-01"));
+    assert_eq!(build.llvm_major, Some(18));
+    assert!(parse_channel_manifest("date = \"2024-05-03\"\n[pkg.cargo]\nversion = \"1.0\"\n").is_err());
+
+    // Mirror layout: dist/<date>/channel-rust-<channel>.toml
+    let mirror = TempDir::new().unwrap();
+    for (date, channel, version, hash) in [
+        ("2024-05-04", "nightly", "1.80.0-nightly (9c9b56879 2024-05-02)", nightly_hash),
+        ("2024-05-03", "nightly", "1.80.0-nightly (9c9b56879 2024-05-02)", nightly_hash),
+        ("2024-05-03", "beta", "1.79.0-beta.4 (a269819ac 2024-05-01)", beta_hash),
+    ] {
+        let dir = mirror.path().join("dist").join(date);
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = channel_manifest(date, version, hash);
+        std::fs::write(dir.join(format!("channel-rust-{}.toml", channel)), manifest).unwrap();
+    }
+    std::fs::write(mirror.path().join("dist").join("channel-rust-nightly.toml.sha256"), "not a manifest").unwrap();
+
+    let builds = import_channel_dir(mirror.path()).unwrap();
+    assert_eq!(builds.toolchains.len(), 2);
+    // The earliest nightly shipping a commit names it.
+    assert_eq!(builds.toolchains[nightly_hash].version, "nightly-2024-05-03");
+    assert_eq!(builds.toolchains[nightly_hash].date.as_deref(), Some("2024-05-02"));
+    assert_eq!(builds.toolchains[beta_hash].version, "1.79.0-beta.4");
+}
+
+/// Minimal ELF64 relocatable file with the given sections.
+fn minimal_elf(sections: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut shstrtab = vec![0u8];
+    let mut names = Vec::new();
+    for (name, _) in sections.iter().copied().chain([(".shstrtab", &[][..])]) {
+        names.push(shstrtab.len() as u32);
+        shstrtab.extend_from_slice(name.as_bytes());
+        shstrtab.push(0);
+    }
+
+    let mut data = Vec::new();
+    let mut ranges = Vec::new();
+    for (_, bytes) in sections.iter().copied().chain([("", &shstrtab[..])]) {
+        ranges.push((64 + data.len() as u64, bytes.len() as u64));
+        data.extend_from_slice(bytes);
+    }
+    while data.len() % 8 != 0 {
+        data.push(0);
+    }
+    let section_count = sections.len() as u16 + 2;
+
+    let mut elf = Vec::new();
+    elf.extend_from_slice(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+    elf.extend_from_slice(&1u16.to_le_bytes()); // ET_REL
+    elf.extend_from_slice(&0x3eu16.to_le_bytes()); // x86-64
+    elf.extend_from_slice(&1u32.to_le_bytes());
+    elf.extend_from_slice(&0u64.to_le_bytes()); // entry
+    elf.extend_from_slice(&0u64.to_le_bytes()); // program headers
+    elf.extend_from_slice(&(64 + data.len() as u64).to_le_bytes());
+    elf.extend_from_slice(&0u32.to_le_bytes());
+    for value in [64u16, 0, 0, 64, section_count, section_count - 1] {
+        elf.extend_from_slice(&value.to_le_bytes());
+    }
+    elf.extend_from_slice(&data);
+
+    elf.extend_from_slice(&[0u8; 64]);
+    // A `.symtab` links to the `.strtab` holding its names.
+    let strtab = sections.iter().position(|(name, _)| *name == ".strtab").map_or(0, |i| i as u32 + 1);
+    for (i, (offset, size)) in ranges.into_iter().enumerate() {
+        let name = sections.get(i).map_or(".shstrtab", |(name, _)| name);
+        let (section_type, link, entsize): (u32, u32, u64) = match name {
+            ".symtab" => (2, strtab, 24),
+            ".strtab" | ".shstrtab" => (3, 0, 0),
+            _ => (1, 0, 0),
+        };
+        elf.extend_from_slice(&names[i].to_le_bytes());
+        elf.extend_from_slice(&section_type.to_le_bytes());
+        elf.extend_from_slice(&0u64.to_le_bytes()); // flags
+        elf.extend_from_slice(&0u64.to_le_bytes()); // addr
+        elf.extend_from_slice(&offset.to_le_bytes());
+        elf.extend_from_slice(&size.to_le_bytes());
+        elf.extend_from_slice(&link.to_le_bytes());
+        elf.extend_from_slice(&0u32.to_le_bytes());
+        elf.extend_from_slice(&1u64.to_le_bytes());
+        elf.extend_from_slice(&entsize.to_le_bytes());
+    }
+    elf
+}
+
+/// `.symtab` and `.strtab` contents with `local` and `global` Rust functions.
+fn rust_function_symbols(local: usize, global: usize) -> (Vec<u8>, Vec<u8>) {
+    let mut symtab = vec![0u8; 24];
+    let mut strtab = vec![0u8];
+    for i in 0..local + global {
+        let bind: u8 = if i < local { 0 } else { 1 };
+        symtab.extend_from_slice(&(strtab.len() as u32).to_le_bytes());
+        symtab.push(bind << 4 | 2); // STT_FUNC
+        symtab.push(0);
+        symtab.extend_from_slice(&1u16.to_le_bytes());
+        symtab.extend_from_slice(&(0x1000 + 16 * i as u64).to_le_bytes());
+        symtab.extend_from_slice(&16u64.to_le_bytes());
+        strtab.extend_from_slice(format!("_ZN3app4f{}17h0123456789abcdefE\0", i).as_bytes());
+    }
+    (symtab, strtab)
+}
+
+#[test]
+fn test_elf_comment_and_dwarf_producers() {
+    let comment = b"GCC: (GNU) 13.2.0\0Linker: LLD 17.0.6\0rustc version 1.75.0 (82e1608df 2023-12-21)\0";
+    let debug_str = b"src/main.rs\0clang LLVM (rustc version 1.75.0 (82e1608df 2023-12-21))\0/home/dev/app\0";
+    let temp_dir = TempDir::new().unwrap();
+    let binary = temp_dir.path().join("app.o");
+    std::fs::write(&binary, minimal_elf(&[(".comment", comment), (".debug_str", debug_str)])).unwrap();
+
+    let result = analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let values: Vec<(&str, &str)> = result.producers.iter().map(|p| (p.source.as_str(), p.value.as_str())).collect();
+    assert_eq!(
+        values,
+        vec![
+            ("elf_comment", "GCC: (GNU) 13.2.0"),
+            ("elf_comment", "Linker: LLD 17.0.6"),
+            ("elf_comment", "rustc version 1.75.0 (82e1608df 2023-12-21)"),
+            ("dwarf_producer", "clang LLVM (rustc version 1.75.0 (82e1608df 2023-12-21))"),
+        ]
+    );
+    assert_eq!(result.rust_version.as_deref(), Some("1.75.0"));
+    assert_eq!(result.llvm_version.as_deref(), Some("17.0.6"));
+    assert_eq!(result.rustc_hash.as_deref().map(|h| &h[..9]), Some("82e1608df"));
+}
+
+fn wasm_name(out: &mut Vec<u8>, name: &str) {
+    out.push(name.len() as u8);
+    out.extend_from_slice(name.as_bytes());
+}
+
+#[test]
+fn test_wasm_producers_section() {
+    let mut section = Vec::new();
+    wasm_name(&mut section, "producers");
+    section.push(2); // fields
+    wasm_name(&mut section, "language");
+    section.push(1);
+    wasm_name(&mut section, "Rust");
+    wasm_name(&mut section, "");
+    wasm_name(&mut section, "processed-by");
+    section.push(2);
+    wasm_name(&mut section, "rustc");
+    wasm_name(&mut section, "1.79.0-beta.4 (a269819ac 2024-05-01)");
+    wasm_name(&mut section, "clang");
+    wasm_name(&mut section, "18.1.2");
+
+    let mut module = b"\0asm\x01\0\0\0".to_vec();
+    module.extend_from_slice(&[1, 4, 1, 0x60, 0, 0]); // type section: one empty signature
+    module.push(0);
+    module.push(section.len() as u8);
+    module.extend_from_slice(&section);
+
+    let temp_dir = TempDir::new().unwrap();
+    let binary = temp_dir.path().join("app.wasm");
+    std::fs::write(&binary, module).unwrap();
+    let result = analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let values: Vec<&str> = result.producers.iter().map(|p| p.value.as_str()).collect();
+    assert_eq!(
+        values,
+        vec!["language: Rust", "processed-by: rustc 1.79.0-beta.4 (a269819ac 2024-05-01)", "processed-by: clang 18.1.2"]
+    );
+    assert!(result.producers.iter().all(|p| p.source == "wasm_producers"));
+    assert_eq!(result.rust_version.as_deref(), Some("1.79.0-beta.4"));
+    assert_eq!(result.llvm_version.as_deref(), Some("18.1.2"));
+}
+
+#[test]
+fn test_toolchain_evidence() {
+    let filename = "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe";
+    let result = analyze_sample(filename);
+    let evidence = &result.toolchain_evidence;
+    assert_eq!(evidence.rustc_hashes.len(), 1);
+    assert_eq!(evidence.rustc_hashes[0].name, "4d91de4e48198da2e33413efdcd9cd2cc0c46688");
+    assert_eq!(evidence.toolchain_dirs[0].name, "stable-x86_64-pc-windows-msvc");
+    // std sources come from the toolchain's rust-src component.
+    assert!(evidence.build_std);
+    assert!(!evidence.mixed_toolchains);
+
+    let filename = "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe";
+    let result = analyze_sample(filename);
+    assert!(!result.toolchain_evidence.build_std);
+    assert!(result.toolchain_evidence.toolchain_dirs.is_empty());
+
+    // Prebuilt objects from an older toolchain linked into a build-std nightly build.
+    let content = [
+        "/home/dev/.rustup/toolchains/nightly-2031-01-01-x86_64-unknown-linux-gnu/lib/rustlib/src/rust/library/core/src/fmt/mod.rs",
+        "/home/dev/.rustup/toolchains/nightly-2031-01-01-x86_64-unknown-linux-gnu/lib/rustlib/src/rust/library/std/src/io/mod.rs",
+        "/rustc/ffffffffffffffffffffffffffffffffffffffff/library/core/src/str/mod.rs",
+        "/rustc/84c898d65adf2f39a5a98507f1fe0ce10a2b8dbc/library/core/src/fmt/mod.rs",
+        "/rustc/84c898d65adf2f39a5a98507f1fe0ce10a2b8dbc/library/alloc/src/vec/mod.rs",
+    ]
+    .join("\0");
+    let temp_dir = TempDir::new().unwrap();
+    let binary = temp_dir.path().join("mixed.bin");
+    std::fs::write(&binary, content).unwrap();
+    let result = analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let evidence = &result.toolchain_evidence;
+    let hashes: Vec<(&str, usize)> = evidence.rustc_hashes.iter().map(|h| (h.name.as_str(), h.count)).collect();
+    assert_eq!(
+        hashes,
+        vec![("84c898d65adf2f39a5a98507f1fe0ce10a2b8dbc", 2), ("ffffffffffffffffffffffffffffffffffffffff", 1)]
+    );
+    assert_eq!(evidence.toolchain_dirs[0].count, 2);
+    assert!(evidence.build_std);
+    assert!(evidence.mixed_toolchains);
+    // The most common hash is reported as the binary's rustc.
+    assert_eq!(result.rust_version.as_deref(), Some("1.69.0"));
+    assert_eq!(evidence.toolchain_dir_version().as_deref(), Some("nightly-2031-01-01"));
+}
+
+#[test]
+fn test_version_estimate() {
+    // Rust 1.63.0: `library/` layout, old `sys/windows` and the pre-1.73 panic message.
+    let filename = "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe";
+    let content = std::fs::read(sample_path(filename)).unwrap();
+    let result = analyze_sample(filename);
+    let estimate = estimate_rust_version(&content, &result.packages).unwrap().unwrap();
+    assert_eq!(estimate.min.as_deref(), Some("1.47"));
+    assert_eq!(estimate.max.as_deref(), Some("1.72"));
+    assert!(estimate.consistent);
+    assert!(estimate.reasons.iter().any(|r| r.signal == "panic_format"));
+
+    // Rust 1.85.0: `sync/poison` is the newest layout change.
+    let filename = "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe";
+    let content = std::fs::read(sample_path(filename)).unwrap();
+    let result = analyze_sample(filename);
+    let estimate = estimate_rust_version(&content, &result.packages).unwrap().unwrap();
+    assert_eq!(estimate.min.as_deref(), Some("1.84"));
+    assert_eq!(estimate.max, None);
+    assert!(estimate.consistent);
+
+    // No hash to resolve: the estimate is reported instead, and a crate MSRV
+    // newer than the std layout allows is flagged as inconsistent.
+    let content = [
+        "/checkout/src/libstd/io/mod.rs",
+        "/home/dev/.cargo/registry/src/github.com-1ecc6299db9ec823/tokio-1.39.2/src/lib.rs",
+    ]
+    .join("\0");
+    let temp_dir = TempDir::new().unwrap();
+    let binary = temp_dir.path().join("old.bin");
+    std::fs::write(&binary, content).unwrap();
+    let result = analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    assert_eq!(result.rust_version, None);
+    let estimate = result.version_estimate.unwrap();
+    assert_eq!(estimate.max.as_deref(), Some("1.46"));
+    assert!(!estimate.consistent);
+    assert!(estimate.reasons.iter().any(|r| r.signal == "crate_msrv" && r.evidence.contains("tokio")));
+}
+
+#[test]
+fn test_target_inference() {
+    let filename = "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe";
+    let result = analyze_sample(filename);
+    assert_eq!(result.target.triple.as_deref(), Some("x86_64-pc-windows-msvc"));
+    assert_eq!(result.target.bits, Some(64));
+    assert!(result.target.evidence.iter().any(|e| e.field == "env" && e.source == "PE Rich header"));
+
+    let filename = "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe";
+    let result = analyze_sample(filename);
+    assert_eq!(result.target.triple.as_deref(), Some("i686-pc-windows-msvc"));
+    assert_eq!(result.target.bits, Some(32));
+
+    // Dynamically linked against musl.
+    let filename = "c18b24be70e5227a6b383c94034210b85c809fba8eca7a06b8b2136d510efee5.elf";
+    let result = analyze_sample(filename);
+    assert_eq!(result.target.triple.as_deref(), Some("mips-unknown-linux-musl"));
+    assert_eq!(result.target.env.as_deref(), Some("musl"));
+
+    let temp_dir = TempDir::new().unwrap();
+    let binary = temp_dir.path().join("static.elf");
+    std::fs::write(&binary, minimal_elf(&[(".rodata", b"/home/dev/musl-1.2.3/src/env/__libc_start_main.c\0")])).unwrap();
+    let result = analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    assert_eq!(result.target.triple.as_deref(), Some("x86_64-unknown-linux-musl"));
+
+    // std `os::android` narrows a Linux ELF down to Android.
+    let path = b"/rustc/84c898d65adf2f39a5a98507f1fe0ce10a2b8dbc/library/std/src/os/android/net.rs\0";
+    std::fs::write(&binary, minimal_elf(&[(".rodata", path)])).unwrap();
+    let result = analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    assert_eq!(result.target.os.as_deref(), Some("android"));
+    assert_eq!(result.target.triple.as_deref(), Some("x86_64-linux-android"));
+
+    // BSD toolchains often leave the ELF OS ABI at System V.
+    let path = b"/rustc/84c898d65adf2f39a5a98507f1fe0ce10a2b8dbc/library/std/src/os/netbsd/fs.rs\0";
+    std::fs::write(&binary, minimal_elf(&[(".rodata", path)])).unwrap();
+    let result = analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    assert_eq!(result.target.triple.as_deref(), Some("x86_64-unknown-netbsd"));
+    assert_eq!(result.target.env, None);
+}
+
+#[test]
+fn test_build_profile() {
+    let filename = "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe";
+    let profile = analyze_sample(filename).build_profile;
+    assert_eq!(profile.profile.as_deref(), Some("release"));
+    assert!(profile.evidence.iter().any(|e| e.setting == "profile" && e.source.contains("target\\release")));
+    assert!(!profile.overflow_checks);
+    assert_eq!(profile.panic_strategy.as_deref(), Some("unwind"));
+    assert_eq!(profile.debuginfo.as_deref(), Some("external"));
+
+    // Overflow checks left on, and panic = "abort".
+    let filename = "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe";
+    let profile = analyze_sample(filename).build_profile;
+    assert!(profile.overflow_checks);
+    assert_eq!(profile.profile.as_deref(), Some("debug"));
+    assert_eq!(profile.panic_strategy.as_deref(), Some("abort"));
+
+    let filename = "c18b24be70e5227a6b383c94034210b85c809fba8eca7a06b8b2136d510efee5.elf";
+    let profile = analyze_sample(filename).build_profile;
+    assert_eq!(profile.stripped, Some(true));
+    assert_eq!(profile.lto, None);
+    assert_eq!(profile.debuginfo.as_deref(), Some("none"));
+
+    // A compile unit of the user's own crate means it was built with debuginfo.
+    let temp_dir = TempDir::new().unwrap();
+    let binary = temp_dir.path().join("debuginfo.elf");
+    let units = b"/rustc/84c898d65adf2f39a5a98507f1fe0ce10a2b8dbc/library/std/src/lib.rs/@/std.5e7f2a6f-cgu.0\0src/main.rs/@/app.1a2b3c4d-cgu.0\0";
+    std::fs::write(&binary, minimal_elf(&[(".debug_str", units)])).unwrap();
+    let profile = analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap().build_profile;
+    assert_eq!(profile.debuginfo.as_deref(), Some("full"));
+    assert!(profile.evidence.iter().any(|e| e.setting == "debuginfo" && e.source.ends_with("crate app")));
+
+    // LTO leaves many internal Rust functions and next to no exported ones.
+    let lto = |local: usize, global: usize| {
+        let (symtab, strtab) = rust_function_symbols(local, global);
+        std::fs::write(&binary, minimal_elf(&[(".text", &[0xc3]), (".symtab", &symtab), (".strtab", &strtab)])).unwrap();
+        analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap().build_profile.lto
+    };
+    assert_eq!(lto(400, 1), Some(true));
+    assert_eq!(lto(400, 40), Some(false));
+    // Too few functions to tell LTO from a small binary.
+    assert_eq!(lto(10, 1), None);
+}
+
+#[test]
+fn test_frameworks() {
+    let filename = "8ac509a776a326180877dc44636081e21f58c89431477ef2a38db10ad6bd15d1.exe";
+    let result = analyze_sample(filename);
+    let find = |name: &str| result.frameworks.iter().find(|f| f.name == name);
+    let tokio = find("tokio").expect("tokio runtime");
+    assert_eq!(tokio.category, "async_runtime");
+    assert!(tokio.variants.contains(&"multi_thread".to_string()));
+    assert_eq!(find("hyper").unwrap().category, "web");
+    assert_eq!(find("system").unwrap().evidence, vec!["default"]);
+
+    // A custom global allocator replaces the system default.
+    let content = [
+        "/home/dev/.cargo/registry/src/index.crates.io-6f17d22bba15001f/tikv-jemallocator-0.5.4/src/lib.rs",
+        "/home/dev/.cargo/registry/src/index.crates.io-6f17d22bba15001f/axum-0.7.5/src/routing/mod.rs",
+        "<jemalloc>: Invalid conf pair",
+        "/rustc/84c898d65adf2f39a5a98507f1fe0ce10a2b8dbc/library/std/src/alloc.rs",
+    ]
+    .join("\0");
+    let temp_dir = TempDir::new().unwrap();
+    let binary = temp_dir.path().join("server.bin");
+    std::fs::write(&binary, content).unwrap();
+    let mut result = analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    let names: Vec<(&str, &str)> = result.frameworks.iter().map(|f| (f.category.as_str(), f.name.as_str())).collect();
+    assert_eq!(names, vec![("allocator", "jemalloc"), ("web", "axum")]);
+    assert_eq!(result.frameworks[0].evidence, vec!["crate:tikv-jemallocator", "string:<jemalloc>: "]);
+
+    // Crate evidence goes with the packages the confidence filter drops.
+    result.retain_min_confidence(1.0);
+    assert!(result.packages.is_empty());
+    let names: Vec<(&str, &str)> = result.frameworks.iter().map(|f| (f.category.as_str(), f.name.as_str())).collect();
+    assert_eq!(names, vec![("allocator", "jemalloc")]);
+    assert_eq!(result.frameworks[0].evidence, vec!["string:<jemalloc>: "]);
+
+    // Without the allocator string the system allocator takes over again.
+    let mut result = analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap();
+    result.frameworks[0].evidence.retain(|e| e.starts_with("crate:"));
+    result.retain_min_confidence(1.0);
+    let names: Vec<(&str, &str)> = result.frameworks.iter().map(|f| (f.category.as_str(), f.name.as_str())).collect();
+    assert_eq!(names, vec![("allocator", "system")]);
+}
+
+#[test]
+fn test_build_environment() {
+    // std's own dependencies carry the Rust project's GitHub Actions cargo home.
+    let filename = "855f411bd0667b650c4f2fd3c9fbb4fa9209cf40b0d655fa9304dcdd956e0808.exe";
+    let env = analyze_sample(filename).build_environment;
+    assert_eq!(env.ci, None);
+    assert_eq!(env.container, None);
+    assert_eq!(env.host_os.as_deref(), Some("windows"));
+    assert_eq!(env.cargo_home.as_deref(), Some("C:\\Users\\user\\.cargo"));
+    assert_eq!(env.user_names, vec!["user"]);
+    assert!(env.developer_workstation);
+
+    let temp_dir = TempDir::new().unwrap();
+    let binary = temp_dir.path().join("env.bin");
+    let analyze = |paths: &[&str]| {
+        std::fs::write(&binary, paths.join("\0")).unwrap();
+        analyze_binary(binary.to_str().unwrap(), DEFAULT_MIN_STR_LEN).unwrap()
+    };
+
+    let result = analyze(&[
+        "/home/runner/work/tool/tool/src/main.rs",
+        "/home/runner/.cargo/registry/src/index.crates.io-6f17d22bba15001f/clap-4.5.4/src/lib.rs",
+    ]);
+    let env = &result.build_environment;
+    assert_eq!(env.ci.as_deref(), Some("github_actions"));
+    assert_eq!(env.host_os.as_deref(), Some("linux"));
+    assert!(env.user_names.is_empty());
+    assert!(!env.developer_workstation);
+
+    // The official Docker image keeps its cargo home outside any `.cargo` directory.
+    let registry_path = "/usr/local/cargo/registry/src/index.crates.io-6f17d22bba15001f/serde-1.0.200/src/de/mod.rs";
+    let result = analyze(&["/app/src/main.rs", registry_path]);
+    assert_eq!(result.build_environment.container.as_deref(), Some("rust_docker_image"));
+    assert_eq!(result.build_environment.cargo_home.as_deref(), Some("/usr/local/cargo"));
+    assert!(result.framework_source_paths.contains(registry_path));
+    assert_eq!(result.user_source_paths, HashSet::from(["/app/src/main.rs".to_string()]));
+
+    let result = analyze(&["/nix/store/0c9kpah5cbqjvhdhi6xz9c29pmcgblxy-source/src/main.rs"]);
+    assert_eq!(result.build_environment.container.as_deref(), Some("nix"));
+    assert_eq!(result.user_source_paths.len(), 1);
+
+    // Sources under /root keep their framework classification.
+    let result = analyze(&[
+        "/root/project/src/main.rs",
+        "/root/.cargo/registry/src/index.crates.io-6f17d22bba15001f/anyhow-1.0.80/src/lib.rs",
+    ]);
+    assert_eq!(result.build_environment.container.as_deref(), Some("root_container"));
+    assert!(result.framework_source_paths.contains("/root/project/src/main.rs"));
+    assert!(result.user_source_paths.is_empty());
+}